@@ -0,0 +1,15 @@
+// Behaviour that would otherwise require inline event handlers, which are blocked by the CSP.
+(function () {
+  if (typeof ClipboardJS !== "undefined") {
+    new ClipboardJS(".clipboard");
+  }
+
+  document.addEventListener("click", function (event) {
+    var target = event.target.closest("[data-select-on-click]");
+
+    if (target) {
+      target.focus();
+      target.select();
+    }
+  });
+})();
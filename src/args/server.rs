@@ -1,7 +1,15 @@
+use crate::crypto::Key;
+use crate::helper::parse_size;
 use crate::restic::repository::cache::RepositoryLocation;
 use crate::{
-    http::{CookieParameters, SessionCache},
-    restic::{repository::cache::RepositoryCache, restore::RestoreManager},
+    http::{CookieParameters, SessionCache, SessionStore, TrustedProxies},
+    restic::{
+        repository::cache::RepositoryCache,
+        restore::{
+            storage::{LocalStorage, S3Config, S3Storage, Storage},
+            ArchiveLayout, ConcurrencyLimits, RestoreManager, RetentionPolicy, StorageLimits,
+        },
+    },
 };
 use argon2::password_hash::PasswordHashString;
 use axum::http::uri::PathAndQuery;
@@ -10,7 +18,14 @@ use axum::{
     Extension, Router,
 };
 use clap::Parser;
-use std::{net::SocketAddr, path::PathBuf, time::Duration};
+use reqwest::Url;
+use std::{
+    fs, io,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 #[derive(Clone)]
 pub struct SiteUrl(pub String);
@@ -33,16 +48,98 @@ pub struct Args {
     #[arg(env, long, default_value_t = 15)]
     session_lifetime_mins: u64,
 
-    /// How long restores will be available.
+    /// Extend sessions on every request instead of expiring them a fixed time after login.
+    #[arg(env, long, default_value_t = false)]
+    session_sliding: bool,
+
+    /// Addresses of reverse proxies whose `X-Forwarded-For` header is trusted, separated by commas.
+    /// Without any, clients are identified by the address they connect from.
+    #[arg(env, long, value_delimiter = ',', verbatim_doc_comment)]
+    trusted_proxies: Vec<IpAddr>,
+
+    /// Directory in which sessions are persisted so they survive server restarts.
+    /// Repository passwords are stored encrypted and can only be recovered using the matching session cookie.
+    /// When omitted, sessions are only kept in memory.
+    #[arg(env, long, verbatim_doc_comment)]
+    session_store: Option<PathBuf>,
+
+    /// How long restores will be available after they were last downloaded, or created if they never were.
     #[arg(env, long, default_value_t = 7)]
     restore_lifetime_days: u32,
 
+    /// How long restores will be available at most, regardless of how recently they were downloaded.
+    #[arg(env, long)]
+    restore_max_age_days: Option<u32>,
+
     /// Temporary directory where restores will be placed.
     /// Contents can become quite large so using a `tmpfs` is not recommended!
     #[arg(env, verbatim_doc_comment)]
     restore_location: PathBuf,
 
-    /// Whether to keep the full directory hierarchy in restore archives or start at the restored folder
+    /// File containing the secret from which restore encryption keys are derived, created if missing.
+    /// Must be kept outside the restore location, ideally on a different volume, as anyone able to read
+    /// both can decrypt the restores.
+    #[arg(env, long, verbatim_doc_comment)]
+    restore_secret: PathBuf,
+
+    /// Total amount of disk space restores may occupy, e.g. `50G`.
+    /// Least recently downloaded restores are evicted to make room for new ones.
+    #[arg(env, long, value_parser = parse_size, verbatim_doc_comment)]
+    restore_quota: Option<u64>,
+
+    /// Largest restore which may be created, e.g. `10G`.
+    #[arg(env, long, value_parser = parse_size)]
+    restore_max_size: Option<u64>,
+
+    /// Number of restores which are written at the same time, others wait in a queue.
+    #[arg(env, long, default_value_t = 2)]
+    restore_workers: usize,
+
+    /// Number of restores a single session may have written at the same time.
+    #[arg(env, long)]
+    restore_workers_per_user: Option<usize>,
+
+    /// URL of an S3 compatible service (e.g. MinIO) to store restores in instead of the restore location.
+    /// The restore location is then only used to stage restores until they are uploaded.
+    #[arg(
+        env,
+        long,
+        requires = "s3_bucket",
+        requires = "s3_access_key",
+        requires = "s3_secret_key",
+        verbatim_doc_comment
+    )]
+    s3_endpoint: Option<Url>,
+
+    /// Bucket in which restores are stored, addressed path-style below the endpoint.
+    #[arg(env, long)]
+    s3_bucket: Option<String>,
+
+    #[arg(env, long, default_value = "us-east-1")]
+    s3_region: String,
+
+    #[arg(env, long)]
+    s3_access_key: Option<String>,
+
+    #[arg(env, long)]
+    s3_secret_key: Option<String>,
+
+    /// Prefix prepended to the keys of all objects, e.g. `restic-dl/`.
+    #[arg(env, long, default_value = "")]
+    s3_prefix: String,
+
+    /// Let clients download restores straight from the bucket through presigned URLs.
+    /// Restores are then stored unencrypted as they can not be decrypted on the way.
+    /// Shared restores are still passed through the server, so that the limits of their links apply.
+    #[arg(env, long, default_value_t = false, verbatim_doc_comment)]
+    s3_presign: bool,
+
+    /// Layout of restore archives unless users choose another one:
+    /// `relative` to the restored folder, `full` paths or wrapped in a folder named after the snapshot date
+    #[arg(env, long, default_value = "relative", verbatim_doc_comment)]
+    archive_layout: ArchiveLayout,
+
+    /// Deprecated, same as `--archive-layout full`
     #[arg(env, long, default_value_t = false)]
     keep_full_paths: bool,
 
@@ -76,6 +173,50 @@ impl Args {
         Duration::from_secs(60 * self.session_lifetime_mins)
     }
 
+    fn restore_storage(&self) -> Arc<dyn Storage> {
+        let Some(endpoint) = self.s3_endpoint.clone() else {
+            return Arc::new(
+                LocalStorage::new(&self.restore_location)
+                    .expect("Failed to prepare restore location"),
+            );
+        };
+
+        let config = S3Config {
+            endpoint,
+            bucket: self.s3_bucket.clone().unwrap_or_default(),
+            region: self.s3_region.clone(),
+            access_key: self.s3_access_key.clone().unwrap_or_default(),
+            secret_key: self.s3_secret_key.clone().unwrap_or_default(),
+            prefix: self.s3_prefix.clone(),
+            presign: self.s3_presign,
+        };
+
+        Arc::new(
+            S3Storage::new(config, self.restore_location.join("staging"))
+                .expect("Failed to prepare S3 storage"),
+        )
+    }
+
+    /// Loads the restore secret, refusing one which sits next to the data it protects.
+    fn restore_secret(&self) -> io::Result<Key> {
+        fs::create_dir_all(&self.restore_location)?;
+
+        let location = self.restore_location.canonicalize()?;
+        let directory = match self.restore_secret.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        if directory.canonicalize()?.starts_with(&location) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "restore secret must be stored outside of the restore location",
+            ));
+        }
+
+        Key::load_or_generate(&self.restore_secret)
+    }
+
     fn cookie_parameters(&self) -> CookieParameters {
         let scheme = self.site_url.scheme().expect(
             "Site URL does not include a protocol, did you forget to prepend http:// or https://?",
@@ -84,6 +225,7 @@ impl Args {
         CookieParameters {
             lifetime: self.session_lifetime(),
             secure: *scheme == Scheme::HTTPS,
+            sliding: self.session_sliding,
         }
     }
 
@@ -138,18 +280,42 @@ impl Args {
 
         let site_url = SiteUrl(self.site_url());
         let cookie_params = self.cookie_parameters();
-        let cache_repo = RepositoryCache::new(self.locations(), session_lifetime);
-        let cache_session = SessionCache::new(self.password, session_lifetime);
-        let manager = RestoreManager::new(
-            self.restore_location,
-            self.restore_lifetime_days,
-            self.keep_full_paths,
-        )
-        .expect("Failed to prepare restore location");
+        let trusted_proxies = TrustedProxies(Arc::new(self.trusted_proxies.clone()));
+        let storage = self.restore_storage();
+        let secret = self
+            .restore_secret()
+            .expect("Failed to load restore secret");
+        let store = self
+            .session_store
+            .as_ref()
+            .map(|path| SessionStore::new(path).expect("Failed to prepare session store location"));
+        let cache_repo = RepositoryCache::new(self.locations(), session_lifetime, store.clone());
+        let cache_session =
+            SessionCache::new(self.password, session_lifetime, self.session_sliding, store);
+        let limits = StorageLimits {
+            total: self.restore_quota,
+            per_restore: self.restore_max_size,
+        };
+        let retention = RetentionPolicy {
+            idle_days: self.restore_lifetime_days,
+            max_age_days: self.restore_max_age_days,
+        };
+        let concurrency = ConcurrencyLimits {
+            workers: self.restore_workers,
+            per_user: self.restore_workers_per_user,
+        };
+        let layout = if self.keep_full_paths {
+            ArchiveLayout::Full
+        } else {
+            self.archive_layout
+        };
+        let manager = RestoreManager::new(storage, retention, layout, limits, concurrency, secret)
+            .expect("Failed to prepare restore location");
 
         router
             .layer(Extension(site_url))
             .layer(Extension(cookie_params))
+            .layer(Extension(trusted_proxies))
             .layer(Extension(cache_repo))
             .layer(Extension(cache_session))
             .layer(Extension(manager))
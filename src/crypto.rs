@@ -0,0 +1,195 @@
+//! Small symmetric encryption helpers.
+//!
+//! Small secrets are sealed with XChaCha20-Poly1305 using a random nonce, keys are derived with BLAKE3.
+//!
+//! For large files which need random access, [`StreamCipher`] exposes the seekable XChaCha20 keystream.
+//! It provides no integrity protection on its own and each key and nonce must only be used for a single
+//! stream which is written front to back, as rewriting any part of it would reuse the keystream.
+
+use chacha20::{
+    cipher::{KeyIvInit, StreamCipher as _, StreamCipherSeek},
+    XChaCha20,
+};
+use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305, XNonce};
+use rand::{rngs::OsRng, RngCore};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+pub const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+
+#[derive(Clone)]
+pub struct Key([u8; blake3::KEY_LEN]);
+
+impl Key {
+    pub fn generate() -> Self {
+        let mut key = [0; blake3::KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    /// Derives a new key for the given purpose from one or more pieces of key material.
+    /// The context string should be hardcoded, globally unique, and application-specific.
+    pub fn derive(context: &str, material: &[&[u8]]) -> Self {
+        let mut hasher = blake3::Hasher::new_derive_key(context);
+
+        for part in material {
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+
+        Self(*hasher.finalize().as_bytes())
+    }
+
+    /// Reads a key from disk or generates and persists a new one if the file does not exist yet.
+    pub fn load_or_generate(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        match fs::read(path) {
+            Ok(bytes) => Ok(Self(bytes.try_into().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "key file has invalid length")
+            })?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let key = Self::generate();
+                let mut options = fs::File::options();
+                options.write(true).create_new(true);
+
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+                options.open(path)?.write_all(&key.0)?;
+                Ok(key)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8; blake3::KEY_LEN] {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+/// Encrypts and authenticates the plaintext, returning `nonce || ciphertext || tag`.
+pub fn seal(key: &Key, plaintext: &[u8]) -> Vec<u8> {
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = XChaCha20Poly1305::new(key.as_bytes().into())
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .expect("plaintext exceeds the maximum message length");
+
+    let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    sealed
+}
+
+/// Reverses [`seal`], returning `None` if the data has been tampered with or the key is wrong.
+pub fn open(key: &Key, sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return None;
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+    XChaCha20Poly1305::new(key.as_bytes().into())
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .ok()
+}
+
+/// Seekable keystream for encrypting data of arbitrary length.
+pub struct StreamCipher {
+    cipher: XChaCha20,
+}
+
+impl StreamCipher {
+    pub fn new(key: &Key, nonce: &[u8; NONCE_LEN]) -> Self {
+        Self {
+            cipher: XChaCha20::new(key.as_bytes().into(), nonce.into()),
+        }
+    }
+
+    /// Moves to the given byte offset within the stream.
+    pub fn seek(&mut self, position: u64) {
+        self.cipher.seek(position);
+    }
+
+    /// Encrypts or decrypts the data in place, advancing the position accordingly.
+    pub fn apply(&mut self, data: &mut [u8]) {
+        self.cipher.apply_keystream(data);
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn roundtrip_sealed_data() {
+        let key = Key::generate();
+        let sealed = seal(&key, b"correct horse battery staple");
+
+        assert_ne!(
+            &sealed[NONCE_LEN..sealed.len() - TAG_LEN],
+            b"correct horse battery staple"
+        );
+        assert_eq!(
+            open(&key, &sealed).unwrap(),
+            b"correct horse battery staple"
+        );
+    }
+
+    #[test]
+    fn reject_tampered_or_foreign_data() {
+        let key = Key::generate();
+        let mut sealed = seal(&key, b"secret");
+
+        assert!(open(&Key::generate(), &sealed).is_none());
+
+        sealed[NONCE_LEN] ^= 1;
+        assert!(open(&key, &sealed).is_none());
+        assert!(open(&key, &sealed[..NONCE_LEN]).is_none());
+    }
+
+    #[test]
+    fn seek_within_stream() {
+        let key = Key::generate();
+        let nonce = [7; NONCE_LEN];
+        let mut data = *b"The quick brown fox jumps over the lazy dog, twice over and over again!";
+
+        let mut cipher = StreamCipher::new(&key, &nonce);
+        cipher.apply(&mut data);
+        assert_ne!(&data[..9], b"The quick");
+
+        // Decrypt the second half first to check that positions line up
+        let mut cipher = StreamCipher::new(&key, &nonce);
+        cipher.seek(40);
+        cipher.apply(&mut data[40..]);
+        cipher.seek(0);
+        cipher.apply(&mut data[..40]);
+
+        assert_eq!(
+            &data,
+            b"The quick brown fox jumps over the lazy dog, twice over and over again!"
+        );
+    }
+
+    #[test]
+    fn derive_distinct_keys_for_distinct_material() {
+        let a = Key::derive("test", &[b"ab", b"c"]);
+        let b = Key::derive("test", &[b"a", b"bc"]);
+        let c = Key::derive("other", &[b"ab", b"c"]);
+
+        assert_ne!(a.as_bytes(), b.as_bytes());
+        assert_ne!(a.as_bytes(), c.as_bytes());
+    }
+}
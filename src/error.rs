@@ -1,6 +1,9 @@
 use axum::{http::StatusCode, response::IntoResponse};
 use rustic_core::RusticError;
-use std::io;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,12 +13,62 @@ pub enum Error {
 
     #[error("i/o error: {0}")]
     IoError(#[from] io::Error),
+
+    #[error("insufficient storage: {0}")]
+    InsufficientStorage(String),
+
+    #[error("restore has been cancelled")]
+    Cancelled,
+
+    #[error("invalid filter: {0}")]
+    InvalidFilter(String),
+
+    #[error("{}: {source}", .path.display())]
+    Entry {
+        path: PathBuf,
+        #[source]
+        source: Box<Error>,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// Attributes the error to the entry at the given path, unless it already is.
+    pub fn at(self, path: impl AsRef<Path>) -> Self {
+        match self {
+            Error::Entry { .. } | Error::Cancelled => self,
+            _ => Error::Entry {
+                path: path.as_ref().to_owned(),
+                source: Box::new(self),
+            },
+        }
+    }
+
+    /// The error without the entry it has been attributed to.
+    pub fn root(&self) -> &Self {
+        match self {
+            Error::Entry { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Entry { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
-        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+        let status = match self {
+            Error::InsufficientStorage(_) => StatusCode::INSUFFICIENT_STORAGE,
+            Error::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status, self.to_string()).into_response()
     }
 }
@@ -10,6 +10,34 @@ pub fn path_to_url(path: &Path) -> String {
         .join("/")
 }
 
+/// Value of a `Content-Disposition` header offering the data as a download under the given name.
+///
+/// The name is passed as an ASCII-only fallback as well as percent-encoded UTF-8 (RFC 5987), so that
+/// quotes, semicolons and the like within it can not break out of the parameter.
+pub fn content_disposition(file_name: &str) -> String {
+    let fallback = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            ' '..='~' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+
+    let encoded = file_name
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => (byte as char).to_string(),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect::<String>();
+
+    format!(r#"attachment; filename="{fallback}"; filename*=UTF-8''{encoded}"#)
+}
+
 pub mod filters {
     use chrono::{DateTime, Local};
 
@@ -60,6 +88,32 @@ pub fn format_size(bytes: u64) -> String {
     }
 }
 
+/// Parses a byte count with an optional decimal unit suffix like `500M` or `2 GB`.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid size `{input}`"))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" => 1000,
+        "M" => 1000_u64.pow(2),
+        "G" => 1000_u64.pow(3),
+        "T" => 1000_u64.pow(4),
+        _ => return Err(format!("Unknown unit in size `{input}`")),
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Size `{input}` is too large"))
+}
+
 pub fn format_time(date: DateTime<Local>) -> String {
     let distance = Local::now().signed_duration_since(date);
 
@@ -86,7 +140,7 @@ pub fn format_time(date: DateTime<Local>) -> String {
 
 #[cfg(test)]
 mod does {
-    use super::format_size as f;
+    use super::{content_disposition, format_size as f, parse_size};
 
     #[test]
     fn format_byte_counts_correctly() {
@@ -113,4 +167,33 @@ mod does {
         assert_eq!(f(99_420_000_000_000), "99.42 TB");
         assert_eq!(f(99_500_000_000_000), "100 TB");
     }
+
+    #[test]
+    fn parse_sizes_with_units() {
+        assert_eq!(parse_size("1234"), Ok(1234));
+        assert_eq!(parse_size("5K"), Ok(5_000));
+        assert_eq!(parse_size("500 MB"), Ok(500_000_000));
+        assert_eq!(parse_size("2g"), Ok(2_000_000_000));
+
+        assert!(parse_size("").is_err());
+        assert!(parse_size("1.5G").is_err());
+        assert!(parse_size("3X").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn keep_file_names_within_their_parameter() {
+        assert_eq!(
+            content_disposition("report.pdf"),
+            r#"attachment; filename="report.pdf"; filename*=UTF-8''report.pdf"#
+        );
+        assert_eq!(
+            content_disposition(r#"a"; b=c\.txt"#),
+            r#"attachment; filename="a_; b=c_.txt"; filename*=UTF-8''a%22%3B%20b%3Dc%5C.txt"#
+        );
+        assert_eq!(
+            content_disposition("Übersicht.zip"),
+            r#"attachment; filename="_bersicht.zip"; filename*=UTF-8''%C3%9Cbersicht.zip"#
+        );
+    }
 }
@@ -1,12 +1,17 @@
 use crate::args::ServerArgs;
 use listenfd::ListenFd;
+use std::net::SocketAddr;
 
 mod extract;
 mod middleware;
 mod navigation;
 mod route;
 
-pub use middleware::{repository::CookieParameters, session::SessionCache};
+pub use extract::TrustedProxies;
+pub use middleware::{
+    repository::CookieParameters,
+    session::{SessionCache, SessionInfo, SessionStore},
+};
 use tokio::net::TcpListener;
 
 pub async fn serve(args: ServerArgs) -> std::result::Result<(), Box<dyn std::error::Error>> {
@@ -22,7 +27,11 @@ pub async fn serve(args: ServerArgs) -> std::result::Result<(), Box<dyn std::err
     println!("Listening on {}", listener.local_addr()?);
 
     let app = args.into_layers(route::router());
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
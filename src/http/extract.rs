@@ -1,8 +1,10 @@
+mod client;
 mod entry_path;
 mod htmx;
 mod query;
 mod snapshot;
 
+pub use client::{ClientInfo, TrustedProxies};
 pub use entry_path::EntryPath;
 pub use htmx::HxRequest;
 pub use query::*;
@@ -0,0 +1,112 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+/// Reverse proxies whose `X-Forwarded-For` header is honoured, made available as an extension.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(pub Arc<Vec<IpAddr>>);
+
+/// Information about the client sending a request, used for listing active sessions.
+///
+/// The address is taken from the `X-Forwarded-For` header only if the request comes from a
+/// trusted proxy, since anyone else could claim an arbitrary address. Otherwise, the peer address is used.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip());
+
+        let trusted = parts
+            .extensions
+            .get::<TrustedProxies>()
+            .cloned()
+            .unwrap_or_default();
+
+        let forwarded = header_value(&parts.headers, "x-forwarded-for");
+        let address = client_address(peer, forwarded.as_deref(), &trusted.0);
+
+        let user_agent = header_value(&parts.headers, header::USER_AGENT.as_str());
+
+        Ok(Self {
+            address,
+            user_agent,
+        })
+    }
+}
+
+/// Address of the client, following the `X-Forwarded-For` chain back through trusted proxies only.
+fn client_address(
+    peer: Option<IpAddr>,
+    forwarded: Option<&str>,
+    trusted: &[IpAddr],
+) -> Option<String> {
+    let mut address = peer?;
+
+    // Proxies append the address they received the request from, hence the chain is walked backwards
+    for hop in forwarded.into_iter().flat_map(|f| f.rsplit(',')) {
+        if !trusted.contains(&address) {
+            break;
+        }
+
+        match hop.trim().parse() {
+            Ok(hop) => address = hop,
+            Err(_) => break,
+        }
+    }
+
+    Some(address.to_string())
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string)
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn ignore_forwarded_address_from_untrusted_peers() {
+        let address = client_address(Some(ip("203.0.113.7")), Some("10.0.0.1"), &[]);
+
+        assert_eq!(address.as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn follow_forwarded_addresses_through_trusted_proxies() {
+        let trusted = [ip("127.0.0.1"), ip("10.0.0.2")];
+        let forwarded = Some("198.51.100.1, 203.0.113.7, 10.0.0.2");
+
+        let address = client_address(Some(ip("127.0.0.1")), forwarded, &trusted);
+
+        // The first entry could have been made up by the client
+        assert_eq!(address.as_deref(), Some("203.0.113.7"));
+    }
+}
@@ -41,10 +41,15 @@ macro_rules! boolean_query_param {
 }
 
 boolean_query_param!(Login, "login");
+boolean_query_param!(Logout, "logout");
 boolean_query_param!(Unlock, "unlock");
+boolean_query_param!(Lock, "lock");
 boolean_query_param!(CreateRestore, "restore");
 boolean_query_param!(ShareRestore, "share");
+boolean_query_param!(StreamRestore, "stream");
 boolean_query_param!(Progress, "progress");
+boolean_query_param!(Download, "download");
+boolean_query_param!(Checksum, "checksum");
 
 #[cfg(test)]
 mod does {
@@ -1,3 +1,4 @@
 pub mod repository;
 pub mod restore;
+pub mod security;
 pub mod session;
@@ -1,5 +1,5 @@
 use crate::{
-    http::extract::Unlock,
+    http::extract::{Lock, Unlock},
     restic::repository::{
         cache::{RepositoryCache, SessionId},
         Repository,
@@ -10,18 +10,18 @@ use axum::{
     handler::Handler,
     http::{Method, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
     Extension,
 };
 use axum_extra::extract::CookieJar;
-use cookie::RepositoryCookieExt;
 use serde::Deserialize;
 use unlock::LockedPage;
 
 mod cookie;
+mod lock;
 mod unlock;
 
-pub use cookie::{CookieParameters, RepositoryCookie};
+pub use cookie::{CookieParameters, RepositoryCookie, RepositoryCookieExt};
 
 #[derive(Deserialize)]
 pub struct RepoParam {
@@ -31,10 +31,12 @@ pub struct RepoParam {
 
 pub async fn unlock(
     Extension(cache): Extension<RepositoryCache>,
+    Extension(params): Extension<CookieParameters>,
     Path(param): Path<RepoParam>,
     jar: CookieJar,
     method: Method,
     is_unlock_request: Unlock,
+    is_lock_request: Lock,
     mut request: Request,
     next: Next,
 ) -> Result<Response, (StatusCode, LockedPage)> {
@@ -42,21 +44,32 @@ pub async fn unlock(
         return Ok(unlock::route.call(request, ()).await);
     }
 
-    let repository = extract_repository(&param.repo_name, &jar, &cache)
+    if *is_lock_request && method == Method::POST {
+        return Ok(lock::route.call(request, ()).await);
+    }
+
+    let (id, repository) = extract_repository(&param.repo_name, &jar, &cache)
         .map_err(|_| (StatusCode::UNAUTHORIZED, LockedPage))?;
 
     request.extensions_mut().insert(repository);
 
-    Ok(next.run(request).await)
+    let response = next.run(request).await;
+
+    if params.sliding {
+        let cookie = RepositoryCookie::new(id, &param.repo_name, &params);
+        Ok((jar.add(cookie), response).into_response())
+    } else {
+        Ok(response)
+    }
 }
 
 fn extract_repository(
     name: &str,
     jar: &CookieJar,
     cache: &RepositoryCache,
-) -> Result<Repository, ()> {
+) -> Result<(SessionId, Repository), ()> {
     let cookie = jar.get_repository_cookie(name).ok_or_else(|| ())?;
     let id: SessionId = cookie.try_into().map_err(|_| ())?;
 
-    cache.get(id).ok_or(())
+    cache.get(id).map(|repository| (id, repository)).ok_or(())
 }
@@ -12,12 +12,15 @@ const REPO_COOKIE_PREFIX: &'static str = "repo-";
 pub struct CookieParameters {
     pub lifetime: Duration,
     pub secure: bool,
+    pub sliding: bool,
 }
 
 pub struct RepositoryCookie<'c>(Cookie<'c>);
 
 pub trait RepositoryCookieExt {
     fn get_repository_cookie(&self, repository_name: &str) -> Option<RepositoryCookie>;
+    fn remove_repository_cookie(self, repository_name: &str) -> Self;
+    fn remove_repository_cookies(self) -> Self;
 }
 
 impl CookieParameters {
@@ -53,6 +56,25 @@ impl RepositoryCookieExt for CookieJar {
             .cloned()
             .map(RepositoryCookie)
     }
+
+    fn remove_repository_cookie(self, repository_name: &str) -> Self {
+        let key = format!("{REPO_COOKIE_PREFIX}{repository_name}");
+        let path = format!("/{repository_name}");
+
+        self.remove(Cookie::build((key, "")).path(path))
+    }
+
+    fn remove_repository_cookies(self) -> Self {
+        let names = self
+            .iter()
+            .filter_map(|cookie| cookie.name().strip_prefix(REPO_COOKIE_PREFIX))
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        names
+            .iter()
+            .fold(self, |jar, name| jar.remove_repository_cookie(name))
+    }
 }
 
 impl<'c> TryFrom<RepositoryCookie<'c>> for SessionId {
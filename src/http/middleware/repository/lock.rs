@@ -0,0 +1,28 @@
+use super::{RepoParam, RepositoryCookieExt};
+use crate::restic::repository::cache::{RepositoryCache, SessionId};
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Redirect},
+    Extension,
+};
+use axum_extra::extract::CookieJar;
+
+/// Locks the repository again by evicting the session and removing its cookie.
+pub async fn route(
+    jar: CookieJar,
+    Extension(cache): Extension<RepositoryCache>,
+    Path(param): Path<RepoParam>,
+) -> impl IntoResponse {
+    let session = jar
+        .get_repository_cookie(&param.repo_name)
+        .and_then(|cookie| SessionId::try_from(cookie).ok());
+
+    if let Some(id) = session {
+        cache.remove(id);
+    }
+
+    (
+        jar.remove_repository_cookie(&param.repo_name),
+        Redirect::to("/"),
+    )
+}
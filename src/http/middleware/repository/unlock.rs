@@ -1,6 +1,7 @@
 use super::CookieParameters;
 use crate::{
-    http::middleware::repository::RepositoryCookie, restic::repository::cache::RepositoryCache,
+    http::middleware::repository::RepositoryCookie,
+    restic::repository::cache::{RepositoryCache, SessionId},
     Result,
 };
 use askama::Template;
@@ -32,10 +33,11 @@ pub async fn route(
     uri: OriginalUri,
     Extension(cache): Extension<RepositoryCache>,
     Extension(parameters): Extension<CookieParameters>,
+    Extension(owner): Extension<SessionId>,
     Path(path): Path<RepositoryName>,
     Form(form): Form<RepositoryPassword>,
 ) -> Result<impl IntoResponse> {
-    let (_repo, session_id) = cache.open(&path.name, form.password)?;
+    let (_repo, session_id) = cache.open(&path.name, form.password, owner)?;
     let cookie = RepositoryCookie::new(session_id, &path.name, &parameters);
 
     debug_assert_eq!(_repo.name(), path.name);
@@ -1,15 +1,78 @@
 use crate::{
-    http::extract::{CreateRestore, EntryPath, ShareRestore},
-    restic::{repository::Snapshot, restore::RestoreManager},
+    helper::{content_disposition, parse_size},
+    http::extract::{CreateRestore, EntryPath, ShareRestore, StreamRestore},
+    restic::{
+        repository::{cache::SessionId, Repository, Snapshot},
+        restore::{
+            ArchiveLayout, DataReceiver, RestoreFilter, RestoreId, RestoreManager, RestoreOptions,
+        },
+    },
+    Error, Result,
 };
 use axum::{
-    extract::Request,
+    body::Body,
+    extract::{Query, Request},
     handler::Handler,
-    http::Method,
+    http::{header, HeaderMap, HeaderValue, Method},
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
-    Extension,
+    Extension, Form,
 };
+use futures::{future::ready, stream, StreamExt};
+use serde::Deserialize;
+
+/// Options of a restore, given in the query or, as submitted by the filter dialog, the form.
+#[derive(Deserialize)]
+struct RestoreParams {
+    /// Layout of the archive, the one configured for the server if absent
+    layout: Option<ArchiveLayout>,
+    /// Patterns of entries to restore, one per line
+    include: Option<String>,
+    /// Patterns of entries to leave out, one per line
+    exclude: Option<String>,
+    max_file_size: Option<String>,
+    /// ID of an earlier snapshot, restricting the restore to what changed since
+    base: Option<String>,
+    /// Whether the archive contains a checksum manifest and a description of the snapshot
+    manifest: Option<bool>,
+}
+
+impl RestoreParams {
+    fn or(self, other: Self) -> Self {
+        Self {
+            layout: self.layout.or(other.layout),
+            include: self.include.or(other.include),
+            exclude: self.exclude.or(other.exclude),
+            max_file_size: self.max_file_size.or(other.max_file_size),
+            base: self.base.or(other.base),
+            manifest: self.manifest.or(other.manifest),
+        }
+    }
+}
+
+impl TryFrom<RestoreParams> for RestoreOptions {
+    type Error = Error;
+
+    fn try_from(params: RestoreParams) -> Result<Self> {
+        let max_file_size = params
+            .max_file_size
+            .filter(|size| !size.trim().is_empty())
+            .map(|size| parse_size(&size))
+            .transpose()
+            .map_err(Error::InvalidFilter)?;
+
+        Ok(Self {
+            layout: params.layout,
+            filter: RestoreFilter::from_lines(
+                params.include.as_deref().unwrap_or_default(),
+                params.exclude.as_deref().unwrap_or_default(),
+                max_file_size,
+            ),
+            base: None,
+            manifest: params.manifest.unwrap_or_default(),
+        })
+    }
+}
 
 pub async fn create(
     is_restore_request: CreateRestore,
@@ -28,15 +91,80 @@ async fn handler(
     snapshot: Snapshot,
     path: EntryPath,
     share: ShareRestore,
+    streamed: StreamRestore,
+    Query(query): Query<RestoreParams>,
+    Extension(session): Extension<SessionId>,
     Extension(manager): Extension<RestoreManager>,
-) -> impl IntoResponse {
-    let id = manager.restore(snapshot, &*path).await;
+    Extension(repository): Extension<Repository>,
+    form: Option<Form<RestoreParams>>,
+) -> Result<Response> {
+    let mut params = match form {
+        Some(Form(form)) => form.or(query),
+        None => query,
+    };
+    let base = params.base.take().filter(|id| !id.trim().is_empty());
+    let mut options = RestoreOptions::try_from(params)?;
+    options.base = base.map(|id| repository.snapshot(id.trim())).transpose()?;
+
+    if *streamed && !*share {
+        let (id, receiver) = manager
+            .stream(snapshot, &*path, options, Some(session.handle()))
+            .await?;
+
+        if let Some(receiver) = receiver {
+            if let Some(response) = stream_download(&manager, id, receiver).await? {
+                return Ok(response);
+            }
+        }
+
+        // Already available or being written for someone else, which the download page takes care of
+        return Ok(Redirect::to(&format!("/restore/{id}")).into_response());
+    }
+
+    let id = manager
+        .restore(snapshot, &*path, options, Some(session.handle()))
+        .await?;
 
     let url = if *share {
-        format!("/restore/{id}/share")
+        let share = manager.create_share(id, Some(session.handle())).await?;
+        format!("/restore/{id}/shares/{}", share.token)
     } else {
         format!("/restore/{id}")
     };
 
-    Redirect::to(&url)
+    Ok(Redirect::to(&url).into_response())
+}
+
+/// Responds with the data of the restore while it is being written.
+/// Returns `None` if no data arrives, e.g. because the restore shares existing data or failed right away.
+async fn stream_download(
+    manager: &RestoreManager,
+    id: RestoreId,
+    mut receiver: DataReceiver,
+) -> Result<Option<Response>> {
+    // Nothing is sent before the first chunk, so that the request can still be redirected
+    let Some(Ok(first)) = receiver.recv().await else {
+        return Ok(None);
+    };
+
+    let name = manager.fetch(id).await?.file_name();
+    manager.record_access(id).await?;
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+
+    if let Ok(filename) = HeaderValue::from_str(&content_disposition(&name)) {
+        headers.insert(header::CONTENT_DISPOSITION, filename);
+    }
+
+    let remaining = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    let body = Body::from_stream(stream::once(ready(Ok(first))).chain(remaining));
+
+    Ok(Some((headers, body).into_response()))
 }
@@ -0,0 +1,106 @@
+use crate::http::CookieParameters;
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use rand::{thread_rng, Rng};
+
+const HSTS_MAX_AGE_SECS: u64 = 365 * 24 * 60 * 60;
+
+tokio::task_local! {
+    static NONCE: String;
+}
+
+/// Returns the CSP nonce of the request currently being handled.
+///
+/// Intended to be called from templates so inline scripts can be allowed by the policy.
+/// Yields an empty string when called outside of the [`headers`] middleware.
+pub fn nonce() -> String {
+    NONCE.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// Attaches security related headers like a nonce-based `Content-Security-Policy` to every response.
+pub async fn headers(
+    Extension(params): Extension<CookieParameters>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let nonce = {
+        let mut bytes = [0; 16];
+        thread_rng().fill(&mut bytes);
+        hex::encode(bytes)
+    };
+
+    let mut response = NONCE.scope(nonce.clone(), next.run(request)).await;
+    let headers = response.headers_mut();
+
+    set_default(
+        headers,
+        header::CONTENT_SECURITY_POLICY,
+        content_security_policy(&nonce),
+    );
+    set_default(headers, header::X_FRAME_OPTIONS, "DENY".into());
+    set_default(headers, header::X_CONTENT_TYPE_OPTIONS, "nosniff".into());
+    set_default(headers, header::REFERRER_POLICY, "no-referrer".into());
+    set_default(
+        headers,
+        HeaderName::from_static("permissions-policy"),
+        "camera=(), microphone=(), geolocation=(), payment=(), usb=(), interest-cohort=()".into(),
+    );
+
+    if params.secure {
+        set_default(
+            headers,
+            header::STRICT_TRANSPORT_SECURITY,
+            format!("max-age={HSTS_MAX_AGE_SECS}"),
+        );
+    }
+
+    response
+}
+
+/// Marks the response as uncacheable, used for pages that are only visible to authenticated users.
+pub fn no_store(response: &mut Response) {
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+}
+
+/// Progress updates are delivered as `<style>` fragments through htmx,
+/// which is why inline styles can not be restricted by a nonce.
+fn content_security_policy(nonce: &str) -> String {
+    [
+        "default-src 'none'",
+        &format!("script-src 'self' 'nonce-{nonce}'"),
+        "style-src 'self' 'unsafe-inline'",
+        "img-src 'self' data:",
+        "connect-src 'self'",
+        "form-action 'self'",
+        "frame-ancestors 'none'",
+        "base-uri 'none'",
+    ]
+    .join("; ")
+}
+
+fn set_default(headers: &mut HeaderMap, name: HeaderName, value: String) {
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        headers.entry(name).or_insert(value);
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::content_security_policy;
+
+    #[test]
+    fn embed_nonce_into_script_source() {
+        let policy = content_security_policy("abc123");
+
+        assert!(policy.contains("script-src 'self' 'nonce-abc123'"));
+        assert!(policy.starts_with("default-src 'none'"));
+        assert!(!policy.contains("unsafe-eval"));
+    }
+}
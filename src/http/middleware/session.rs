@@ -1,8 +1,12 @@
 use std::time::Duration;
 
 use crate::{
-    http::{extract::Login, CookieParameters},
-    restic::repository::cache::SessionId,
+    http::{
+        extract::{ClientInfo, Login, Logout},
+        middleware::{repository::RepositoryCookieExt, security},
+        CookieParameters,
+    },
+    restic::repository::cache::{RepositoryCache, SessionId},
 };
 use askama::Template;
 use axum::{
@@ -13,14 +17,16 @@ use axum::{
     response::{IntoResponse, Redirect, Response},
     Extension, Form,
 };
-use axum_extra::extract::CookieJar;
+use axum_extra::extract::{cookie::Cookie, CookieJar};
 
 const SESSION_COOKIE_KEY: &'static str = "session";
 
 mod cache;
+mod store;
 
-pub use cache::SessionCache;
+pub use cache::{SessionCache, SessionInfo};
 use serde::Deserialize;
+pub use store::SessionStore;
 use tokio::time::sleep;
 
 #[derive(Template)]
@@ -32,48 +38,61 @@ struct LoginParam {
     password: String,
 }
 
+/// Rejects requests without a valid session and makes the [`SessionId`] available as an extension.
 pub async fn require(
     Extension(cache): Extension<SessionCache>,
+    Extension(params): Extension<CookieParameters>,
     jar: CookieJar,
+    client: ClientInfo,
     method: Method,
     is_login_request: Login,
-    request: Request,
+    is_logout_request: Logout,
+    mut request: Request,
     next: Next,
 ) -> Response {
     if *is_login_request && method == Method::POST {
         return login.call(request, ()).await;
     }
 
-    let session_valid = jar
+    let session = jar
         .get(SESSION_COOKIE_KEY)
         .and_then(|cookie| cookie.value().parse().ok())
-        .map(|id: SessionId| cache.contains(id))
-        .unwrap_or_default();
+        .filter(|id: &SessionId| cache.touch(*id, &client));
+
+    let Some(id) = session else {
+        return (StatusCode::UNAUTHORIZED, LoginPage).into_response();
+    };
 
-    if !session_valid {
-        (StatusCode::UNAUTHORIZED, LoginPage).into_response()
+    request.extensions_mut().insert(id);
+
+    if *is_logout_request && method == Method::POST {
+        return logout.call(request, ()).await;
+    }
+
+    let mut response = next.run(request).await;
+    security::no_store(&mut response);
+
+    if cache.sliding() {
+        (jar.add(session_cookie(&params, id)), response).into_response()
     } else {
-        next.run(request).await
+        response
     }
 }
 
 async fn login(
     uri: OriginalUri,
     jar: CookieJar,
+    client: ClientInfo,
     Extension(params): Extension<CookieParameters>,
     Extension(cache): Extension<SessionCache>,
     Form(login): Form<LoginParam>,
 ) -> Response {
-    match cache.insert(login.password.as_bytes()) {
-        Some(id) => {
-            let cookie = params.cookie(
-                SESSION_COOKIE_KEY.to_string(),
-                id.to_string(),
-                "/".to_string(),
-            );
-
-            (jar.add(cookie), Redirect::to(&uri.path())).into_response()
-        }
+    match cache.insert(login.password.as_bytes(), &client) {
+        Some(id) => (
+            jar.add(session_cookie(&params, id)),
+            Redirect::to(&uri.path()),
+        )
+            .into_response(),
         // TODO Show error
         None => {
             // Poor mans brute-force prevention ^^
@@ -84,6 +103,31 @@ async fn login(
     }
 }
 
+/// Ends the current session and locks all repositories unlocked through it.
+async fn logout(
+    jar: CookieJar,
+    Extension(id): Extension<SessionId>,
+    Extension(sessions): Extension<SessionCache>,
+    Extension(repositories): Extension<RepositoryCache>,
+) -> impl IntoResponse {
+    sessions.remove(id);
+    repositories.remove_owned_by(&id.handle());
+
+    let jar = jar
+        .remove(Cookie::build((SESSION_COOKIE_KEY, "")).path("/"))
+        .remove_repository_cookies();
+
+    (jar, Redirect::to("/"))
+}
+
+fn session_cookie(params: &CookieParameters, id: SessionId) -> Cookie<'static> {
+    params.cookie(
+        SESSION_COOKIE_KEY.to_string(),
+        id.to_string(),
+        "/".to_string(),
+    )
+}
+
 impl LoginPage {
     fn title(&self) -> &'static str {
         "Login"
@@ -1,7 +1,11 @@
-use crate::restic::repository::cache::SessionId;
+use super::SessionStore;
+use crate::{http::extract::ClientInfo, restic::repository::cache::SessionId};
 use argon2::{password_hash::PasswordHashString, Argon2, PasswordVerifier};
+use chrono::{DateTime, Local};
 use std::{
-    collections::HashSet,
+    cmp::Reverse,
+    collections::HashMap,
+    mem,
     sync::{Arc, Mutex},
     time::Duration,
 };
@@ -11,46 +15,235 @@ use tokio::{task::AbortHandle, time::sleep};
 pub struct SessionCache {
     hash: PasswordHashString,
     lifetime: Duration,
-    entries: Arc<Mutex<HashSet<SessionId>>>,
+    sliding: bool,
+    entries: Arc<Mutex<HashMap<SessionId, CachedSession>>>,
+    store: Option<SessionStore>,
+}
+
+#[derive(Clone)]
+pub struct SessionInfo {
+    pub handle: String,
+    pub address: Option<String>,
+    pub user_agent: Option<String>,
+    pub last_seen: DateTime<Local>,
+}
+
+struct CachedSession {
+    handle: AbortHandle,
+    info: SessionInfo,
 }
 
 impl SessionCache {
-    pub fn new(hash: PasswordHashString, lifetime: Duration) -> Self {
+    pub fn new(
+        hash: PasswordHashString,
+        lifetime: Duration,
+        sliding: bool,
+        store: Option<SessionStore>,
+    ) -> Self {
         Self {
             hash,
             lifetime,
-            entries: Arc::new(Mutex::new(HashSet::new())),
+            sliding,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            store,
         }
     }
 
-    pub fn contains(&self, id: SessionId) -> bool {
-        let entries = self.entries.lock().expect("repo cache poisoned");
-        entries.contains(&id)
+    /// Checks whether the session is valid and records the access.
+    /// If sliding expiry is enabled, the lifetime of the session is reset.
+    pub fn touch(&self, id: SessionId, client: &ClientInfo) -> bool {
+        let mut entries = self.entries.lock().expect("session cache poisoned");
+
+        if let Some(entry) = entries.get_mut(&id) {
+            entry.info.last_seen = Local::now();
+            entry.info.address.clone_from(&client.address);
+            entry.info.user_agent.clone_from(&client.user_agent);
+
+            if self.sliding {
+                let new_handle = self.spawn_lifetime_task(id, self.lifetime);
+                let old_handle = mem::replace(&mut entry.handle, new_handle);
+                old_handle.abort();
+
+                if let Some(store) = &self.store {
+                    store.touch_session(id, &entry.info, self.lifetime);
+                }
+            }
+
+            return true;
+        }
+
+        // Sessions created before a restart are only known to the persistent store
+        let Some(store) = &self.store else {
+            return false;
+        };
+
+        let Some(remaining) = store.session(id) else {
+            return false;
+        };
+
+        let lifetime = if self.sliding {
+            self.lifetime
+        } else {
+            remaining
+        };
+
+        let entry = self.entry(id, client, lifetime);
+
+        if self.sliding {
+            store.insert_session(id, &entry.info, self.lifetime);
+        }
+
+        entries.insert(id, entry);
+
+        true
     }
 
-    pub fn insert(&self, password: &[u8]) -> Option<SessionId> {
+    pub fn insert(&self, password: &[u8], client: &ClientInfo) -> Option<SessionId> {
         Argon2::default()
             .verify_password(password, &self.hash.password_hash())
             .ok()?;
 
         let id = SessionId::new();
+        let entry = self.entry(id, client, self.lifetime);
+
+        if let Some(store) = &self.store {
+            store.insert_session(id, &entry.info, self.lifetime);
+        }
 
-        self.entries.lock().expect("repo cache poisoned").insert(id);
-        self.spawn_lifetime_task(id);
+        self.entries
+            .lock()
+            .expect("session cache poisoned")
+            .insert(id, entry);
 
         Some(id)
     }
 
-    fn spawn_lifetime_task(&self, id: SessionId) -> AbortHandle {
-        tokio::spawn(self.clone().purge_entry(id)).abort_handle()
+    pub fn remove(&self, id: SessionId) {
+        self.entries
+            .lock()
+            .expect("session cache poisoned")
+            .remove(&id);
+
+        if let Some(store) = &self.store {
+            store.remove_session(id);
+        }
     }
 
-    async fn purge_entry(self, id: SessionId) {
-        sleep(self.lifetime).await;
+    /// Removes the session with the given handle, returning whether it existed.
+    /// Persisted sessions are removed as well, even if they have not been used since the server started.
+    pub fn revoke(&self, handle: &str) -> bool {
+        let id = self
+            .entries
+            .lock()
+            .expect("session cache poisoned")
+            .keys()
+            .find(|id| id.handle() == handle)
+            .copied();
 
-        self.entries
+        if let Some(id) = id {
+            self.remove(id);
+        }
+
+        let persisted = self
+            .store
+            .as_ref()
+            .is_some_and(|store| store.remove_session_by_handle(handle));
+
+        id.is_some() || persisted
+    }
+
+    /// Lists all sessions which have been active since the server started, or are persisted
+    /// from before that, most recent first.
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let mut sessions = self
+            .entries
             .lock()
-            .expect("repo cache poisoned")
-            .remove(&id);
+            .expect("session cache poisoned")
+            .values()
+            .map(|entry| entry.info.clone())
+            .collect::<Vec<_>>();
+
+        if let Some(store) = &self.store {
+            let persisted = store
+                .sessions()
+                .into_iter()
+                .filter(|info| !sessions.iter().any(|active| active.handle == info.handle))
+                .collect::<Vec<_>>();
+
+            sessions.extend(persisted);
+        }
+
+        sessions.sort_by_key(|session| Reverse(session.last_seen));
+        sessions
+    }
+
+    pub fn sliding(&self) -> bool {
+        self.sliding
+    }
+
+    fn entry(&self, id: SessionId, client: &ClientInfo, lifetime: Duration) -> CachedSession {
+        CachedSession {
+            handle: self.spawn_lifetime_task(id, lifetime),
+            info: SessionInfo {
+                handle: id.handle(),
+                address: client.address.clone(),
+                user_agent: client.user_agent.clone(),
+                last_seen: Local::now(),
+            },
+        }
+    }
+
+    fn spawn_lifetime_task(&self, id: SessionId, lifetime: Duration) -> AbortHandle {
+        tokio::spawn(self.clone().purge_entry(id, lifetime)).abort_handle()
+    }
+
+    async fn purge_entry(self, id: SessionId, lifetime: Duration) {
+        sleep(lifetime).await;
+        self.remove(id);
+    }
+}
+
+impl Drop for CachedSession {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use argon2::{
+        password_hash::{rand_core::OsRng, SaltString},
+        PasswordHasher,
+    };
+
+    fn cache(store: &SessionStore) -> SessionCache {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(b"password", &salt)
+            .unwrap()
+            .serialize();
+
+        SessionCache::new(hash, Duration::from_secs(60), true, Some(store.clone()))
+    }
+
+    #[tokio::test]
+    async fn revoke_sessions_persisted_before_a_restart() {
+        let root = tempfile::tempdir().unwrap();
+        let store = SessionStore::new(root.path()).unwrap();
+        let client = ClientInfo::default();
+
+        let id = cache(&store).insert(b"password", &client).unwrap();
+
+        // Sessions are only known to the store after a restart, until they are used again
+        let restarted = cache(&store);
+        let sessions = restarted.list();
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].handle, id.handle());
+
+        assert!(restarted.revoke(&id.handle()));
+        assert!(restarted.list().is_empty());
+        assert!(!restarted.touch(id, &client));
     }
 }
@@ -0,0 +1,286 @@
+use super::SessionInfo;
+use crate::{
+    crypto::{self, Key},
+    restic::repository::cache::SessionId,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const KEY_FILE: &str = "server.key";
+const SESSION_DIR: &str = "sessions";
+const REPOSITORY_DIR: &str = "repositories";
+
+/// Records are extended at most this often, as sessions are extended on every request.
+/// Sessions restored after a restart may therefore expire up to this much earlier than they would have.
+const TOUCH_INTERVAL: Duration = Duration::from_secs(60);
+
+const CONTEXT_HANDLE: &str = "restic-dl 2024-06 session handle";
+const CONTEXT_REPOSITORY: &str = "restic-dl 2024-06 repository password";
+
+/// Encrypted on-disk store which allows sessions to survive server restarts.
+///
+/// Session IDs are never written to disk, entries are instead addressed by a keyed hash of the ID.
+/// Repository passwords are encrypted with a key derived from both the server key and the session ID,
+/// so they can only be recovered by someone presenting the matching session cookie.
+#[derive(Clone)]
+pub struct SessionStore {
+    root: PathBuf,
+    key: Arc<Key>,
+    /// Records which have been extended recently, by the time they were written
+    touched: Arc<Mutex<HashMap<PathBuf, Instant>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionRecord {
+    expires_at: DateTime<Utc>,
+    /// Details for listing the session, absent for records which predate listing persisted sessions
+    #[serde(default)]
+    handle: Option<String>,
+    #[serde(default)]
+    address: Option<String>,
+    #[serde(default)]
+    user_agent: Option<String>,
+    #[serde(default)]
+    last_seen: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RepositoryRecord {
+    repository: String,
+    #[serde(with = "hex")]
+    password: Vec<u8>,
+    #[serde(default)]
+    owner: Option<String>,
+    expires_at: DateTime<Utc>,
+}
+
+pub struct RestoredRepository {
+    pub name: String,
+    pub password: String,
+    pub owner: Option<String>,
+    pub remaining: Duration,
+}
+
+impl SessionStore {
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root: PathBuf = root.as_ref().into();
+        fs::create_dir_all(root.join(SESSION_DIR))?;
+        fs::create_dir_all(root.join(REPOSITORY_DIR))?;
+
+        let key = Arc::new(Key::load_or_generate(root.join(KEY_FILE))?);
+        let store = Self {
+            root,
+            key,
+            touched: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        store.purge_expired(SESSION_DIR, |r: &SessionRecord| r.expires_at)?;
+        store.purge_expired(REPOSITORY_DIR, |r: &RepositoryRecord| r.expires_at)?;
+
+        Ok(store)
+    }
+
+    /// Returns the remaining lifetime of a persisted session, if it exists and has not expired.
+    pub fn session(&self, id: SessionId) -> Option<Duration> {
+        let record: SessionRecord = self.read(SESSION_DIR, id)?;
+        remaining(record.expires_at)
+    }
+
+    pub fn insert_session(&self, id: SessionId, info: &SessionInfo, lifetime: Duration) {
+        let record = SessionRecord {
+            expires_at: expiry(lifetime),
+            handle: Some(info.handle.clone()),
+            address: info.address.clone(),
+            user_agent: info.user_agent.clone(),
+            last_seen: Some(info.last_seen.into()),
+        };
+
+        self.write(SESSION_DIR, id, &record);
+    }
+
+    /// Extends the lifetime of a persisted session, unless that has been done recently.
+    pub fn touch_session(&self, id: SessionId, info: &SessionInfo, lifetime: Duration) {
+        if !self.recently_touched(&self.path(SESSION_DIR, id)) {
+            self.insert_session(id, info, lifetime);
+        }
+    }
+
+    pub fn remove_session(&self, id: SessionId) {
+        fs::remove_file(self.path(SESSION_DIR, id)).ok();
+    }
+
+    /// Lists the persisted sessions which have not expired yet, as far as their details have been recorded.
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.records::<SessionRecord>(SESSION_DIR)
+            .filter(|(_, record)| remaining(record.expires_at).is_some())
+            .filter_map(|(_, record)| {
+                Some(SessionInfo {
+                    handle: record.handle?,
+                    address: record.address,
+                    user_agent: record.user_agent,
+                    last_seen: record.last_seen?.into(),
+                })
+            })
+            .collect()
+    }
+
+    /// Removes the persisted session with the given handle, returning whether it existed.
+    /// Sessions are addressed by their ID, which is not known for sessions restored after a restart until they are used.
+    pub fn remove_session_by_handle(&self, handle: &str) -> bool {
+        let mut removed = false;
+
+        for (path, record) in self.records::<SessionRecord>(SESSION_DIR) {
+            if record.handle.as_deref() == Some(handle) {
+                removed |= fs::remove_file(path).is_ok();
+            }
+        }
+
+        removed
+    }
+
+    /// Recovers the name and password of a repository unlocked by the given session.
+    pub fn repository(&self, id: SessionId) -> Option<RestoredRepository> {
+        let record: RepositoryRecord = self.read(REPOSITORY_DIR, id)?;
+        let remaining = remaining(record.expires_at)?;
+        let password = crypto::open(&self.repository_key(id), &record.password)?;
+
+        Some(RestoredRepository {
+            name: record.repository,
+            password: String::from_utf8(password).ok()?,
+            owner: record.owner,
+            remaining,
+        })
+    }
+
+    pub fn insert_repository(
+        &self,
+        id: SessionId,
+        name: &str,
+        password: &str,
+        owner: &str,
+        lifetime: Duration,
+    ) {
+        let record = RepositoryRecord {
+            repository: name.to_string(),
+            password: crypto::seal(&self.repository_key(id), password.as_bytes()),
+            owner: Some(owner.to_string()),
+            expires_at: expiry(lifetime),
+        };
+
+        self.write(REPOSITORY_DIR, id, &record);
+    }
+
+    /// Extends the lifetime of a persisted repository session, unless that has been done recently.
+    pub fn touch_repository(&self, id: SessionId, lifetime: Duration) {
+        if self.recently_touched(&self.path(REPOSITORY_DIR, id)) {
+            return;
+        }
+
+        if let Some(mut record) = self.read::<RepositoryRecord>(REPOSITORY_DIR, id) {
+            record.expires_at = expiry(lifetime);
+            self.write(REPOSITORY_DIR, id, &record);
+        }
+    }
+
+    pub fn remove_repository(&self, id: SessionId) {
+        fs::remove_file(self.path(REPOSITORY_DIR, id)).ok();
+    }
+
+    pub fn remove_repositories_owned_by(&self, owner: &str) {
+        for (path, record) in self.records::<RepositoryRecord>(REPOSITORY_DIR) {
+            if record.owner.as_deref() == Some(owner) {
+                fs::remove_file(path).ok();
+            }
+        }
+    }
+
+    fn repository_key(&self, id: SessionId) -> Key {
+        Key::derive(CONTEXT_REPOSITORY, &[self.key.as_bytes(), id.as_bytes()])
+    }
+
+    fn path(&self, directory: &str, id: SessionId) -> PathBuf {
+        let handle = Key::derive(CONTEXT_HANDLE, &[self.key.as_bytes(), id.as_bytes()]);
+        self.root
+            .join(directory)
+            .join(format!("{}.json", hex::encode(handle.as_bytes())))
+    }
+
+    /// Whether the record has been extended within the [`TOUCH_INTERVAL`], otherwise counts it as extended now.
+    fn recently_touched(&self, path: &Path) -> bool {
+        let mut touched = self.touched.lock().expect("touched records poisoned");
+        touched.retain(|_, at| at.elapsed() < TOUCH_INTERVAL);
+
+        if touched.contains_key(path) {
+            return true;
+        }
+
+        touched.insert(path.to_owned(), Instant::now());
+        false
+    }
+
+    fn read<T: DeserializeOwned>(&self, directory: &str, id: SessionId) -> Option<T> {
+        let file = File::open(self.path(directory, id)).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    /// All readable records in the directory along with their paths.
+    fn records<T: DeserializeOwned>(&self, directory: &str) -> impl Iterator<Item = (PathBuf, T)> {
+        fs::read_dir(self.root.join(directory))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter_map(|path| {
+                let record = serde_json::from_reader(File::open(&path).ok()?).ok()?;
+                Some((path, record))
+            })
+    }
+
+    fn write<T: Serialize>(&self, directory: &str, id: SessionId, record: &T) {
+        let result = File::create(self.path(directory, id))
+            .map_err(serde_json::Error::io)
+            .and_then(|file| serde_json::to_writer(file, record));
+
+        if let Err(err) = result {
+            eprintln!("Failed to persist session: {err}");
+        }
+    }
+
+    fn purge_expired<T: DeserializeOwned>(
+        &self,
+        directory: &str,
+        expires_at: impl Fn(&T) -> DateTime<Utc>,
+    ) -> io::Result<()> {
+        for entry in fs::read_dir(self.root.join(directory))? {
+            let path = entry?.path();
+
+            let expired = File::open(&path)
+                .ok()
+                .and_then(|file| serde_json::from_reader::<_, T>(file).ok())
+                .map(|record| remaining(expires_at(&record)).is_none())
+                .unwrap_or(true);
+
+            if expired {
+                fs::remove_file(path).ok();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn expiry(lifetime: Duration) -> DateTime<Utc> {
+    Utc::now() + TimeDelta::from_std(lifetime).expect("session lifetime out of range")
+}
+
+fn remaining(expires_at: DateTime<Utc>) -> Option<Duration> {
+    expires_at.signed_duration_since(Utc::now()).to_std().ok()
+}
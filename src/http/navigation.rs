@@ -41,6 +41,15 @@ impl Navigation {
     }
 }
 
+impl Default for Navigation {
+    fn default() -> Self {
+        Self {
+            content: String::new(),
+            buttons: None,
+        }
+    }
+}
+
 impl<P: AsRef<Path>> From<(&Snapshot, P)> for Breadcrumbs {
     fn from((snapshot, path): (&Snapshot, P)) -> Self {
         let repository: Breadcrumb = snapshot.repo().into();
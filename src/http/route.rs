@@ -1,12 +1,20 @@
-use axum::Router;
+use crate::http::middleware;
+use axum::{middleware::from_fn, Router};
 
 mod assets;
 mod browse;
 mod restore;
+mod restores;
+mod sessions;
+mod share;
 
 pub fn router() -> Router<()> {
     Router::new()
         .nest("/restore", restore::routes())
+        .nest("/share", share::routes())
+        .merge(restores::routes())
+        .merge(sessions::routes())
         .merge(browse::routes())
         .merge(assets::routes())
+        .layer(from_fn(middleware::security::headers))
 }
@@ -1,6 +1,9 @@
 use crate::http::middleware;
 use axum::{middleware::from_fn, routing::get, Router};
-use tower_http::compression::CompressionLayer;
+use tower_http::compression::{
+    predicate::{DefaultPredicate, NotForContentType, Predicate},
+    CompressionLayer,
+};
 
 mod directories;
 mod repositories;
@@ -16,5 +19,8 @@ pub fn routes() -> Router<()> {
         .layer(from_fn(middleware::repository::unlock))
         .route("/", get(repositories::route))
         .layer(from_fn(middleware::session::require))
-        .layer(CompressionLayer::new())
+        // Restores streamed from the directory pages are compressed already
+        .layer(CompressionLayer::new().compress_when(
+            DefaultPredicate::new().and(NotForContentType::const_new("application/octet-stream")),
+        ))
 }
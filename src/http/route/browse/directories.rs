@@ -4,7 +4,10 @@ use crate::{
         extract::{EntryPath, HxRequest},
         navigation::{Breadcrumbs, Navigation},
     },
-    restic::repository::{Entry, EntryKind, Snapshot},
+    restic::{
+        repository::{Entry, EntryKind, Snapshot},
+        restore::ArchiveLayout,
+    },
     Result,
 };
 use askama::Template;
@@ -39,11 +42,15 @@ pub async fn route(snapshot: Snapshot, path: EntryPath, fragment: HxRequest) ->
 
 #[derive(Template)]
 #[template(path = "browse/directory/partial/buttons.html")]
-struct DirectoryButtons;
+struct DirectoryButtons {
+    layouts: [ArchiveLayout; 3],
+}
 
 pub struct Directory {
     children: Vec<DirectoryEntry>,
     parent: Option<DirectoryEntry>,
+    /// Offered in addition to the default layout when downloading a directory
+    layouts: [ArchiveLayout; 3],
 
     breadcrumbs: Breadcrumbs,
     buttons: DirectoryButtons,
@@ -74,8 +81,11 @@ impl Directory {
         Ok(Self {
             children,
             parent,
+            layouts: ArchiveLayout::ALL,
             breadcrumbs,
-            buttons: DirectoryButtons,
+            buttons: DirectoryButtons {
+                layouts: ArchiveLayout::ALL,
+            },
         })
     }
 
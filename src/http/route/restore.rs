@@ -1,14 +1,26 @@
-use axum::{routing::get, Router};
+use crate::http::middleware;
+use axum::{
+    middleware::from_fn,
+    routing::{get, post},
+    Router,
+};
 use tower_http::compression::CompressionLayer;
 
-mod download;
-mod progress;
+pub(super) mod download;
+mod failed;
+pub(super) mod progress;
 mod share;
 
 pub fn routes() -> Router<()> {
     Router::new()
-        .route("/:id/share", get(share::route))
+        .route(
+            "/:id/shares/:token",
+            get(share::route).post(share::configure),
+        )
+        .route("/:id/shares/:token/revoke", post(share::revoke))
         .route("/:id/progress", get(progress::route))
+        .route("/:id/cancel", post(progress::cancel))
         .layer(CompressionLayer::new())
         .route("/:id", get(download::route))
+        .layer(from_fn(middleware::session::require))
 }
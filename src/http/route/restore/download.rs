@@ -1,18 +1,29 @@
 use std::time::{Duration, Instant};
 
-use super::progress::ProgressPage;
+use super::{
+    failed::{retry_url, FailedPage},
+    progress::ProgressPage,
+};
 use crate::{
-    restic::restore::{Restore, RestoreContent, RestoreId, RestoreManager, RestoreState},
+    helper::content_disposition,
+    http::extract::Checksum,
+    restic::repository::cache::SessionId,
+    restic::restore::{
+        digest_header, DecryptReader, Restore, RestoreId, RestoreManager, RestoreState,
+    },
     Result,
 };
 use axum::Extension;
 use axum::{body::Body, http::header};
 use axum::{extract::Path, http::HeaderMap};
 use axum::{
-    http::HeaderValue,
-    response::{IntoResponse, Response},
+    http::{HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use tokio::{
+    io::{AsyncReadExt, BufReader},
+    time::sleep,
 };
-use tokio::{fs::File, io::BufReader, time::sleep};
 use tokio_util::io::ReaderStream;
 
 const MAX_WAIT_DURATION: Duration = Duration::from_secs(1);
@@ -21,7 +32,56 @@ const CHECK_INTERVAL: Duration = Duration::from_millis(250);
 pub async fn route(
     Path(id): Path<RestoreId>,
     Extension(manager): Extension<RestoreManager>,
-) -> Result<impl IntoResponse> {
+    Extension(session): Extension<SessionId>,
+    checksum: Checksum,
+    headers: HeaderMap,
+) -> Result<Response> {
+    if *checksum {
+        return Ok(manager.fetch(id).await?.into_checksum());
+    }
+
+    let restore = wait_for_restore(&manager, id).await?;
+
+    // Only the owner may cancel or retry the restore
+    let is_owner = restore.is_owned_by(&session.handle());
+
+    match &restore.state {
+        RestoreState::InProgress(progress) => {
+            let mut page = ProgressPage::new(format!("/restore/{id}"), progress.current());
+
+            if is_owner {
+                page = page.with_cancel(format!("/restore/{id}/cancel"));
+
+                if let Some(url) = retry_url(&restore) {
+                    page = page.with_retry(url);
+                }
+            }
+
+            return Ok(page.into_response());
+        }
+        RestoreState::Failed(failure) => {
+            let mut page = FailedPage::new(restore.file_name(), failure.clone());
+
+            if is_owner {
+                page = page.with_retry(&restore);
+            }
+
+            return Ok((StatusCode::INTERNAL_SERVER_ERROR, page).into_response());
+        }
+        RestoreState::Available { .. } => {}
+    }
+
+    if is_initial_request(&headers) {
+        manager.record_access(id).await?;
+    }
+
+    restore
+        .into_download(format!("/restore/{id}"), &headers)
+        .await
+}
+
+/// Gives the restore a moment to complete so that small restores are downloaded right away.
+pub async fn wait_for_restore(manager: &RestoreManager, id: RestoreId) -> Result<Restore> {
     let deadline = Instant::now() + MAX_WAIT_DURATION;
 
     while Instant::now() < deadline {
@@ -37,51 +97,180 @@ pub async fn route(
     Ok(manager.fetch(id).await?)
 }
 
-impl IntoResponse for Restore {
-    fn into_response(self) -> Response {
-        match self.state {
+impl Restore {
+    /// Responds with the checksum file of the data in the format of `b3sum`.
+    pub fn into_checksum(self) -> Response {
+        let Some((name, contents)) = self.checksum_file() else {
+            return (StatusCode::NOT_FOUND, "Restore is not available yet").into_response();
+        };
+
+        let mut headers = HeaderMap::new();
+
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+
+        if let Ok(filename) = HeaderValue::from_str(&content_disposition(&name)) {
+            headers.insert(header::CONTENT_DISPOSITION, filename);
+        }
+
+        (headers, contents).into_response()
+    }
+
+    /// Responds with the restored data, or a progress page which reloads `download_url` once it is available.
+    /// Failed restores are explained without offering a retry, see [`FailedPage::with_retry`].
+    /// Single byte ranges requested through the `Range` header are honoured.
+    pub async fn into_download(
+        self,
+        download_url: String,
+        request: &HeaderMap,
+    ) -> Result<Response> {
+        let name = self.file_name();
+
+        let (data, cipher, hash) = match self.state {
             RestoreState::InProgress(progress) => {
-                ProgressPage::new(self.id, progress.current()).into_response()
+                return Ok(ProgressPage::new(download_url, progress.current()).into_response());
             }
 
-            RestoreState::Available { file, content, .. } => {
-                let source_name = self
-                    .source
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_else(|| String::from("restore"));
+            RestoreState::Available {
+                presigned: Some(url),
+                ..
+            } => return Ok(Redirect::temporary(&url).into_response()),
 
-                let name = match content {
-                    RestoreContent::File { .. } => source_name,
-                    RestoreContent::Archive { .. } => format!("{source_name}.zip"),
-                };
+            RestoreState::Available {
+                data, cipher, hash, ..
+            } => (data, cipher, hash),
+
+            RestoreState::Failed(failure) => {
+                let page = FailedPage::new(name, failure);
+                return Ok((StatusCode::INTERNAL_SERVER_ERROR, page).into_response());
+            }
+        };
 
-                let mut headers = HeaderMap::new();
+        let size = data.size();
+        let mut headers = HeaderMap::new();
+
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+        // Describes the whole data, even if only a range of it is sent
+        for (name, structured) in [("repr-digest", true), ("digest", false)] {
+            headers.insert(
+                HeaderName::from_static(name),
+                HeaderValue::from_str(&digest_header(&hash, structured))
+                    .expect("digest is a valid header value"),
+            );
+        }
+
+        if let Ok(filename) = HeaderValue::from_str(&content_disposition(&name)) {
+            headers.insert(header::CONTENT_DISPOSITION, filename);
+        }
+
+        let range = request
+            .get(header::RANGE)
+            .and_then(|range| range.to_str().ok())
+            .map(|range| {
+                http_range_header::parse_range_header(range).and_then(|r| r.validate(size))
+            });
+
+        let (status, range) = match range {
+            // Multiple ranges would require a multipart response, so we send everything instead
+            Some(Ok(ranges)) if ranges.len() == 1 => {
+                let range = ranges[0].clone();
 
                 headers.insert(
-                    header::CONTENT_TYPE,
-                    HeaderValue::from_static("application/octet-stream"),
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(&format!(
+                        "bytes {}-{}/{size}",
+                        range.start(),
+                        range.end()
+                    ))
+                    .expect("content range is a valid header value"),
                 );
 
-                if let Ok(filename) =
-                    HeaderValue::from_str(&format!(r#"attachment; filename="{name}""#))
-                {
-                    headers.insert(header::CONTENT_DISPOSITION, filename);
-                }
+                (StatusCode::PARTIAL_CONTENT, range)
+            }
+            Some(Err(_)) => {
+                headers.insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes */{size}"))
+                        .expect("content range is a valid header value"),
+                );
 
-                if let Ok(Ok(length)) = file
-                    .metadata()
-                    .map(|m| HeaderValue::from_str(&m.len().to_string()))
-                {
-                    headers.insert(header::CONTENT_LENGTH, length);
-                }
+                return Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response());
+            }
+            _ => (StatusCode::OK, 0..=size.saturating_sub(1)),
+        };
 
-                // TODO Does this support range queries? Probably not as it can't access the header.
-                let body =
-                    Body::from_stream(ReaderStream::new(BufReader::new(File::from_std(file))));
+        let length = if size == 0 {
+            0
+        } else {
+            range.end() - range.start() + 1
+        };
 
-                (headers, body).into_response()
-            }
-        }
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+
+        let reader = DecryptReader::new(
+            data.read_from(*range.start()).await?,
+            cipher,
+            *range.start(),
+        );
+
+        let body = Body::from_stream(ReaderStream::new(BufReader::new(reader).take(length)));
+
+        Ok((status, headers, body).into_response())
+    }
+}
+
+/// Whether the request fetches the restore from the beginning, as opposed to resuming
+/// a previous download, and should therefore count towards the download statistics.
+///
+/// Only a single range starting past the beginning is a resume, anything else is answered
+/// with the data from the beginning or not at all.
+pub fn is_initial_request(request: &HeaderMap) -> bool {
+    let Some(ranges) = request
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| range.trim().strip_prefix("bytes="))
+    else {
+        return true;
+    };
+
+    let mut ranges = ranges.split(',');
+
+    match (ranges.next(), ranges.next()) {
+        (Some(range), None) => match range.split_once('-') {
+            // Suffix ranges such as `bytes=-500` request the end of the data
+            Some((start, _)) if start.trim().is_empty() => false,
+            Some((start, _)) => start.trim().parse::<u64>().map_or(true, |start| start == 0),
+            None => true,
+        },
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn is_initial(range: &'static str) -> bool {
+        let headers = HeaderMap::from_iter([(header::RANGE, HeaderValue::from_static(range))]);
+        is_initial_request(&headers)
+    }
+
+    #[test]
+    fn tell_resumes_from_initial_requests() {
+        assert!(is_initial_request(&HeaderMap::new()));
+        assert!(is_initial("bytes=0-"));
+        assert!(is_initial("bytes=00-99"));
+        assert!(is_initial("bytes=100-199, 300-"));
+
+        assert!(!is_initial("bytes=1-"));
+        assert!(!is_initial("bytes=-100"));
     }
 }
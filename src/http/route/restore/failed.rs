@@ -0,0 +1,85 @@
+use crate::{
+    helper::path_to_url,
+    restic::restore::{Restore, RestoreFailure},
+};
+use askama::Template;
+
+/// Explains that a restore failed, optionally offering to start it over.
+#[derive(Template)]
+#[template(path = "restore/failed.html")]
+pub struct FailedPage {
+    name: String,
+    failure: RestoreFailure,
+    retry_url: Option<String>,
+}
+
+/// URL to which a POST request starts the restore over, if its origin is known.
+pub fn retry_url(restore: &Restore) -> Option<String> {
+    // Retrying goes through the regular restore creation so that locked repositories get unlocked first
+    let repository = restore.repository.as_ref()?;
+    let snapshot = restore.snapshot.as_ref()?;
+
+    let mut url = format!(
+        "/{repository}/{}/{}?restore",
+        &snapshot[0..8],
+        path_to_url(&restore.source)
+    );
+
+    // The options are part of the identity of the restore, hence have to be repeated
+    let mut options = form_urlencoded::Serializer::new(String::new());
+
+    if let Some(layout) = restore.layout {
+        options.append_pair("layout", layout.as_str());
+    }
+
+    let filter = &restore.filter;
+
+    if !filter.include.is_empty() {
+        options.append_pair("include", &filter.include.join("\n"));
+    }
+
+    if !filter.exclude.is_empty() {
+        options.append_pair("exclude", &filter.exclude.join("\n"));
+    }
+
+    if let Some(size) = filter.max_file_size {
+        options.append_pair("max_file_size", &size.to_string());
+    }
+
+    if let Some(base) = &restore.base_snapshot {
+        options.append_pair("base", &base[0..8]);
+    }
+
+    if restore.manifest {
+        options.append_pair("manifest", "true");
+    }
+
+    let options = options.finish();
+
+    if !options.is_empty() {
+        url.push('&');
+        url.push_str(&options);
+    }
+
+    Some(url)
+}
+
+impl FailedPage {
+    pub fn new(name: String, failure: RestoreFailure) -> Self {
+        Self {
+            name,
+            failure,
+            retry_url: None,
+        }
+    }
+
+    /// Offers to retry the restore, which should only be done for its owner.
+    pub fn with_retry(mut self, restore: &Restore) -> Self {
+        self.retry_url = retry_url(restore);
+        self
+    }
+
+    pub fn title(&self) -> &str {
+        "Restore failed"
+    }
+}
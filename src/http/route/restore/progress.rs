@@ -1,9 +1,12 @@
 use crate::{
-    restic::restore::{RestoreId, RestoreManager},
+    restic::{
+        repository::cache::SessionId,
+        restore::{RestoreId, RestoreManager},
+    },
     Result,
 };
-use askama_axum::IntoResponse;
-use axum::{extract::Path, Extension};
+use askama_axum::{IntoResponse, Response};
+use axum::{extract::Path, http::StatusCode, response::Redirect, Extension};
 
 mod fragment;
 mod page;
@@ -17,3 +20,18 @@ pub async fn route(
 ) -> Result<impl IntoResponse> {
     Ok(manager.progress(id)?)
 }
+
+/// Stops the restore, after which its page explains that it has been cancelled.
+/// Only the session which requested the restore may cancel it, as others might be waiting for it too.
+pub async fn cancel(
+    Path(id): Path<RestoreId>,
+    Extension(manager): Extension<RestoreManager>,
+    Extension(session): Extension<SessionId>,
+) -> Result<Response> {
+    if !manager.fetch(id).await?.is_owned_by(&session.handle()) {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    manager.cancel(id)?;
+    Ok(Redirect::to(&format!("/restore/{id}")).into_response())
+}
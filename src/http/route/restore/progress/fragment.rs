@@ -1,6 +1,9 @@
 use crate::{
     helper::filters,
-    restic::restore::progress::{Progress, Status},
+    restic::restore::{
+        progress::{Progress, Status},
+        RestoreFailure,
+    },
 };
 use askama::Template;
 use axum::response::sse::Event;
@@ -9,6 +12,8 @@ use axum::response::sse::Event;
 struct StatusData {
     progress: usize,
     status: Status,
+    /// Entries skipped by a tolerant restore, if any
+    errors: Option<u64>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -35,6 +40,7 @@ pub struct ProgressFragment {
     bytes: Option<BytesData>,
     directories: Option<DirectoriesData>,
     files: Option<FilesData>,
+    failure: Option<RestoreFailure>,
 }
 
 impl ProgressFragment {
@@ -43,6 +49,12 @@ impl ProgressFragment {
             && self.bytes.is_none()
             && self.directories.is_none()
             && self.files.is_none()
+            && self.failure.is_none()
+    }
+
+    /// Whether the restore failed, in which case the page should stay to explain why.
+    pub fn is_failed(&self) -> bool {
+        self.failure.is_some()
     }
 
     pub fn delta(&self, previous: &Self) -> Self {
@@ -60,6 +72,7 @@ impl ProgressFragment {
         clear_if_equal!(bytes);
         clear_if_equal!(directories);
         clear_if_equal!(files);
+        clear_if_equal!(failure);
 
         data
     }
@@ -76,6 +89,7 @@ impl From<Progress> for ProgressFragment {
     fn from(progress: Progress) -> ProgressFragment {
         let status = Some(StatusData {
             status: progress.status,
+            errors: (progress.errors > 0).then_some(progress.errors),
             progress: (progress.data.percentage() * 100.0) as usize,
         });
 
@@ -98,6 +112,7 @@ impl From<Progress> for ProgressFragment {
             bytes,
             directories,
             files,
+            failure: progress.failure,
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::restic::restore::{progress::Progress, RestoreId};
+use crate::restic::restore::progress::Progress;
 
 use super::fragment::ProgressFragment;
 use askama::Template;
@@ -8,6 +8,8 @@ use askama::Template;
 pub struct ProgressPage {
     refresh_url: String,
     sse_url: String,
+    cancel_url: Option<String>,
+    retry_url: Option<String>,
     data: ProgressFragment,
 }
 
@@ -17,11 +19,26 @@ impl ProgressPage {
         format!("Restore progress")
     }
 
-    pub fn new(id: RestoreId, progress: Progress) -> Self {
+    /// Creates a progress page for the restore downloadable at `download_url`.
+    pub fn new(download_url: String, progress: Progress) -> Self {
         Self {
-            refresh_url: format!("/restore/{id}"),
-            sse_url: format!("/restore/{id}/progress"),
+            sse_url: format!("{download_url}/progress"),
+            refresh_url: download_url,
+            cancel_url: None,
+            retry_url: None,
             data: progress.into(),
         }
     }
+
+    /// Offers to cancel the restore through a POST request to the given URL.
+    pub fn with_cancel(mut self, cancel_url: String) -> Self {
+        self.cancel_url = Some(cancel_url);
+        self
+    }
+
+    /// Offers to start the restore over through a POST request to the given URL, once it failed.
+    pub fn with_retry(mut self, retry_url: String) -> Self {
+        self.retry_url = Some(retry_url);
+        self
+    }
 }
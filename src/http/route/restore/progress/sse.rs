@@ -5,7 +5,13 @@ use axum::response::{sse::Event, Response, Sse};
 use futures::{stream, StreamExt};
 use futures_time::stream::StreamExt as _;
 use futures_time::time::Duration;
-use std::convert::Infallible;
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 use tokio::sync::broadcast::error::RecvError;
 
 impl IntoResponse for ProgressReceiver {
@@ -21,6 +27,8 @@ impl IntoResponse for ProgressReceiver {
         });
 
         let mut previous = ProgressFragment::default();
+        let failed = Arc::new(AtomicBool::new(false));
+        let failed_at_end = failed.clone();
 
         let event_stream = update_stream
             // Ensure that we don't overload the client while making sure to always emit something
@@ -31,6 +39,7 @@ impl IntoResponse for ProgressReceiver {
             .filter_map(|buffer| async move { buffer.into_iter().last() })
             // Calculate deltas in comparison to the last sent state
             .map(move |data| {
+                failed.store(data.is_failed(), Ordering::Relaxed);
                 let delta = data.delta(&previous);
                 previous = data;
                 delta
@@ -42,10 +51,15 @@ impl IntoResponse for ProgressReceiver {
             })
             .map(Event::from)
             // Send a `goodbye` message that reloads the page (i.e. downloads the restore)
+            // Failures are explained in place instead, only the connection is closed for good
             .chain(stream::once(async move {
-                Event::default().event("reload").data(
-                    r#"<script>ca.classList.add("active"); window.location.reload();</script>"#,
-                )
+                let script = if failed_at_end.load(Ordering::Relaxed) {
+                    "<div></div>"
+                } else {
+                    r#"<script>ca.classList.add("active"); window.location.reload();</script>"#
+                };
+
+                Event::default().event("reload").data(script)
             }))
             .map(std::result::Result::<Event, Infallible>::Ok);
 
@@ -1,24 +1,124 @@
 use crate::args::SiteUrl;
+use crate::helper::filters;
 use crate::http::extract::HxRequest;
-use crate::restic::restore::RestoreId;
+use crate::restic::restore::{RestoreId, RestoreManager, Share, SharePolicy, ShareToken};
+use crate::Result;
 use askama::Template;
 use askama_axum::{IntoResponse, Response};
 use axum::extract::Path;
-use axum::Extension;
+use axum::{Extension, Form};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
 use fast_qr::convert::{svg::SvgBuilder, Builder, Shape};
 use fast_qr::qr::QRBuilder;
+use serde::Deserialize;
+use std::io;
 
 pub async fn route(
     fragment: HxRequest,
-    Path(id): Path<RestoreId>,
+    Path((id, token)): Path<(RestoreId, ShareToken)>,
     Extension(SiteUrl(site_url)): Extension<SiteUrl>,
-) -> Response {
-    let url = format!("{site_url}/restore/{id}");
+    Extension(manager): Extension<RestoreManager>,
+) -> Result<Response> {
+    let share = fetch_share(&manager, id, token).await?;
 
-    if *fragment {
-        ShareFragment::new(url).into_response()
-    } else {
-        SharePage::new(url).into_response()
+    Ok(SharePartial::new(site_url, &share).into_response(*fragment))
+}
+
+/// Updates the restrictions of the share link and shows the resulting link again.
+pub async fn configure(
+    fragment: HxRequest,
+    Path((id, token)): Path<(RestoreId, ShareToken)>,
+    Extension(SiteUrl(site_url)): Extension<SiteUrl>,
+    Extension(manager): Extension<RestoreManager>,
+    Form(form): Form<PolicyForm>,
+) -> Result<Response> {
+    let share = fetch_share(&manager, id, token).await?;
+    let message = form.message();
+    let policy = form.into_policy(&share.policy).await?;
+    let share = manager.configure_share(token, policy, message).await?;
+
+    Ok(SharePartial::new(site_url, &share).into_response(*fragment))
+}
+
+pub async fn revoke(
+    fragment: HxRequest,
+    Path((id, token)): Path<(RestoreId, ShareToken)>,
+    Extension(SiteUrl(site_url)): Extension<SiteUrl>,
+    Extension(manager): Extension<RestoreManager>,
+) -> Result<Response> {
+    fetch_share(&manager, id, token).await?;
+    let share = manager.revoke_share(token).await?;
+
+    Ok(SharePartial::new(site_url, &share).into_response(*fragment))
+}
+
+async fn fetch_share(
+    manager: &RestoreManager,
+    id: RestoreId,
+    token: ShareToken,
+) -> io::Result<Share> {
+    let share = manager.share(token).await?;
+
+    if share.restore != id {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "share not found"));
+    }
+
+    Ok(share)
+}
+
+#[derive(Deserialize)]
+pub struct PolicyForm {
+    #[serde(default)]
+    expires_on: String,
+    #[serde(default)]
+    max_downloads: String,
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    remove_password: Option<String>,
+    #[serde(default)]
+    message: String,
+}
+
+impl PolicyForm {
+    fn message(&self) -> Option<String> {
+        Some(self.message.trim())
+            .filter(|message| !message.is_empty())
+            .map(ToString::to_string)
+    }
+
+    async fn into_policy(self, previous: &SharePolicy) -> io::Result<SharePolicy> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+        let expires_at = match self.expires_on.trim() {
+            "" => None,
+            date => Some(
+                NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| invalid("invalid expiry date"))?
+                    .and_time(NaiveTime::MIN)
+                    .and_utc()
+                    + chrono::TimeDelta::days(1),
+            ),
+        };
+
+        let max_downloads = match self.max_downloads.trim() {
+            "" => None,
+            count => Some(
+                count
+                    .parse::<u64>()
+                    .map_err(|_| invalid("invalid download limit"))?,
+            ),
+        };
+
+        let policy = if !self.password.is_empty() {
+            SharePolicy::new(expires_at, max_downloads, Some(self.password)).await
+        } else if self.remove_password.is_some() {
+            SharePolicy::new(expires_at, max_downloads, None).await
+        } else {
+            previous.with_limits(expires_at, max_downloads)
+        };
+
+        Ok(policy)
     }
 }
 
@@ -37,30 +137,30 @@ pub struct ShareFragment {
 struct SharePartial {
     url: String,
     svg: String,
+    action: String,
+
+    expires_on: String,
+    max_downloads: String,
+    has_password: bool,
+    message: String,
+
+    downloads: u64,
+    created_at: DateTime<Local>,
+    last_access: Option<DateTime<Local>>,
+    revoked_at: Option<DateTime<Local>>,
 }
 
 impl SharePage {
     pub fn title(&self) -> &str {
         "Share link"
     }
-
-    pub fn new(url: String) -> Self {
-        Self {
-            share: SharePartial::new(url),
-        }
-    }
-}
-
-impl ShareFragment {
-    pub fn new(url: String) -> Self {
-        Self {
-            share: SharePartial::new(url),
-        }
-    }
 }
 
 impl SharePartial {
-    fn new(url: String) -> Self {
+    fn new(site_url: String, share: &Share) -> Self {
+        let url = format!("{site_url}/share/{}", share.token);
+        let policy = &share.policy;
+
         // Since we are using a somewhat fixed length of data, we use expect here
         let qrcode = QRBuilder::new(url.as_bytes())
             .build()
@@ -70,6 +170,39 @@ impl SharePartial {
             .shape(Shape::RoundedSquare)
             .to_str(&qrcode);
 
-        Self { url, svg }
+        // The expiry is stored as the exclusive end of the selected day
+        let expires_on = policy
+            .expires_at
+            .map(|expiry| {
+                (expiry - chrono::TimeDelta::days(1))
+                    .format("%Y-%m-%d")
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        Self {
+            url,
+            svg,
+            action: format!("/restore/{}/shares/{}", share.restore, share.token),
+            expires_on,
+            max_downloads: policy
+                .max_downloads
+                .map(|max| max.to_string())
+                .unwrap_or_default(),
+            has_password: policy.has_password(),
+            message: share.message.clone().unwrap_or_default(),
+            downloads: share.downloads,
+            created_at: share.created_at.into(),
+            last_access: share.last_access.map(Into::into),
+            revoked_at: share.revoked_at.map(Into::into),
+        }
+    }
+
+    fn into_response(self, fragment: bool) -> Response {
+        if fragment {
+            ShareFragment { share: self }.into_response()
+        } else {
+            SharePage { share: self }.into_response()
+        }
     }
 }
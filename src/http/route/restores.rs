@@ -0,0 +1,110 @@
+use crate::{
+    helper::filters,
+    http::{middleware, navigation::Navigation},
+    restic::restore::{RestoreContent, RestoreId, RestoreManager, RestoreSummary},
+    Result,
+};
+use askama::Template;
+use axum::{
+    extract::Path,
+    middleware::from_fn,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Extension, Router,
+};
+use chrono::{DateTime, Local};
+use tower_http::compression::CompressionLayer;
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route("/restores", get(list))
+        .route("/restores/:id/revoke", post(revoke))
+        .route("/restores/:id/extend", post(extend))
+        .route("/restores/:id/pin", post(pin))
+        .route("/restores/:id/unpin", post(unpin))
+        .route("/restores/:id/delete", post(delete))
+        .layer(from_fn(middleware::session::require))
+        .layer(CompressionLayer::new())
+}
+
+async fn list(Extension(manager): Extension<RestoreManager>) -> Result<impl IntoResponse> {
+    let restores = manager
+        .list()
+        .await?
+        .into_iter()
+        .map(RestoreRow::from)
+        .collect();
+
+    Ok(RestoresPage { restores })
+}
+
+/// Revokes all share links of a restore while keeping the restore itself.
+async fn revoke(
+    Path(id): Path<RestoreId>,
+    Extension(manager): Extension<RestoreManager>,
+) -> Result<impl IntoResponse> {
+    manager.revoke_shares(id).await?;
+    Ok(Redirect::to("/restores"))
+}
+
+async fn extend(
+    Path(id): Path<RestoreId>,
+    Extension(manager): Extension<RestoreManager>,
+) -> Result<impl IntoResponse> {
+    manager.extend(id).await?;
+    Ok(Redirect::to("/restores"))
+}
+
+async fn pin(
+    Path(id): Path<RestoreId>,
+    Extension(manager): Extension<RestoreManager>,
+) -> Result<impl IntoResponse> {
+    manager.pin(id, true).await?;
+    Ok(Redirect::to("/restores"))
+}
+
+async fn unpin(
+    Path(id): Path<RestoreId>,
+    Extension(manager): Extension<RestoreManager>,
+) -> Result<impl IntoResponse> {
+    manager.pin(id, false).await?;
+    Ok(Redirect::to("/restores"))
+}
+
+async fn delete(
+    Path(id): Path<RestoreId>,
+    Extension(manager): Extension<RestoreManager>,
+) -> Result<impl IntoResponse> {
+    manager.delete(id).await?;
+    Ok(Redirect::to("/restores"))
+}
+
+#[derive(Template)]
+#[template(path = "restores/page.html")]
+struct RestoresPage {
+    restores: Vec<RestoreRow>,
+}
+
+struct RestoreRow {
+    summary: RestoreSummary,
+    created_at: DateTime<Local>,
+    expires_at: Option<DateTime<Local>>,
+    last_access: Option<DateTime<Local>>,
+}
+
+impl From<RestoreSummary> for RestoreRow {
+    fn from(summary: RestoreSummary) -> Self {
+        Self {
+            created_at: summary.created_at.into(),
+            expires_at: summary.expires_at.map(Into::into),
+            last_access: summary.last_access.map(Into::into),
+            summary,
+        }
+    }
+}
+
+impl RestoresPage {
+    fn title(&self) -> &'static str {
+        "Restores"
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{
+    helper::filters,
+    http::{middleware, navigation::Navigation, SessionCache, SessionInfo},
+    restic::repository::cache::{RepositoryCache, SessionId},
+};
+use askama::Template;
+use axum::{
+    extract::Path,
+    middleware::from_fn,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Extension, Router,
+};
+use tower_http::compression::CompressionLayer;
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route("/sessions", get(list))
+        .route("/sessions/:handle", post(revoke))
+        .layer(from_fn(middleware::session::require))
+        .layer(CompressionLayer::new())
+}
+
+async fn list(
+    Extension(current): Extension<SessionId>,
+    Extension(sessions): Extension<SessionCache>,
+    Extension(repositories): Extension<RepositoryCache>,
+) -> impl IntoResponse {
+    let current = current.handle();
+
+    let sessions = sessions
+        .list()
+        .into_iter()
+        .map(|info| SessionRow {
+            current: info.handle == current,
+            repositories: repositories.unlocked_by(&info.handle),
+            info,
+        })
+        .collect();
+
+    SessionsPage { sessions }
+}
+
+/// Ends another session remotely and locks all repositories it unlocked.
+async fn revoke(
+    Path(handle): Path<String>,
+    Extension(sessions): Extension<SessionCache>,
+    Extension(repositories): Extension<RepositoryCache>,
+) -> impl IntoResponse {
+    if sessions.revoke(&handle) {
+        repositories.remove_owned_by(&handle);
+    }
+
+    Redirect::to("/sessions")
+}
+
+#[derive(Template)]
+#[template(path = "sessions/page.html")]
+struct SessionsPage {
+    sessions: Vec<SessionRow>,
+}
+
+struct SessionRow {
+    info: SessionInfo,
+    current: bool,
+    repositories: Vec<String>,
+}
+
+impl SessionsPage {
+    fn title(&self) -> &'static str {
+        "Sessions"
+    }
+}
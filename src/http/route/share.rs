@@ -0,0 +1,14 @@
+use axum::{routing::get, Router};
+use tower_http::compression::CompressionLayer;
+
+mod download;
+mod landing;
+mod policy;
+
+/// Public routes through which restores are made available to people without a session.
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route("/:token/progress", get(download::progress))
+        .layer(CompressionLayer::new())
+        .route("/:token", get(download::route).post(download::route))
+}
@@ -0,0 +1,289 @@
+use super::landing::LandingPage;
+use crate::{
+    http::{
+        extract::{Checksum, Download},
+        middleware::security,
+        route::restore::download::{is_initial_request, wait_for_restore},
+        CookieParameters,
+    },
+    restic::restore::{
+        PolicyViolation, RestoreManager, RestoreState, Share, ShareToken, TICKET_LIFETIME,
+    },
+    Result,
+};
+use askama_axum::{IntoResponse, Response};
+use axum::{extract::Path, http::HeaderMap, Extension, Form};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::sleep;
+
+const RESUME_COOKIE_KEY: &str = "share-resume";
+const ACCESS_COOKIE_KEY: &str = "share-access";
+
+#[derive(Deserialize)]
+pub struct DownloadParam {
+    password: Option<String>,
+}
+
+/// Shows the landing page of a share or, with `?download`, the restored data itself and, with `?checksum`, its checksum file.
+pub async fn route(
+    is_download: Download,
+    checksum: Checksum,
+    Path(token): Path<ShareToken>,
+    Extension(manager): Extension<RestoreManager>,
+    Extension(params): Extension<CookieParameters>,
+    jar: CookieJar,
+    headers: HeaderMap,
+    form: Option<Form<DownloadParam>>,
+) -> Result<Response> {
+    let password = form.and_then(|Form(param)| param.password);
+    let share = manager.share(token).await?;
+
+    let checked = check_access(
+        &manager,
+        &share,
+        password.as_deref(),
+        *is_download,
+        &headers,
+        &jar,
+    )
+    .await;
+
+    let is_resume = match checked {
+        Ok(is_resume) => is_resume,
+        Err(violation) => {
+            if violation == PolicyViolation::PasswordInvalid {
+                // Same brute-force prevention as for the login
+                sleep(Duration::from_secs(1)).await;
+            }
+
+            return Ok(violation.into_response());
+        }
+    };
+
+    // The progress of the restore is followed without the password
+    let jar = match share.policy.has_password() {
+        true => jar.add(ticket_cookie(
+            &params,
+            token,
+            ACCESS_COOKIE_KEY,
+            manager.access_ticket(token),
+        )),
+        false => jar,
+    };
+
+    if *checksum {
+        let response = manager.fetch(share.restore).await?.into_checksum();
+        return Ok((jar, response).into_response());
+    }
+
+    if !*is_download {
+        let restore = manager.fetch(share.restore).await?;
+        let mut response = LandingPage::new(&share, &restore, password).into_response();
+
+        // The page might contain the password for the download form
+        security::no_store(&mut response);
+
+        return Ok((jar, response).into_response());
+    }
+
+    let mut restore = wait_for_restore(&manager, share.restore).await?;
+
+    // Presigned URLs could be used again and again, bypassing the expiry and download limit of the share
+    if let RestoreState::Available { presigned, .. } = &mut restore.state {
+        *presigned = None;
+    }
+
+    let is_counted = !is_resume && matches!(restore.state, RestoreState::Available { .. });
+
+    if is_counted {
+        if let Err(violation) = manager.record_share_access(token).await? {
+            return Ok(violation.into_response());
+        }
+    }
+
+    let response = restore
+        .into_download(format!("/share/{token}"), &headers)
+        .await?;
+
+    if !is_counted {
+        return Ok((jar, response).into_response());
+    }
+
+    // Lets the client resume this download without counting it again
+    let jar = jar.add(ticket_cookie(
+        &params,
+        token,
+        RESUME_COOKIE_KEY,
+        manager.resume_ticket(token),
+    ));
+
+    Ok((jar, response).into_response())
+}
+
+/// Shows the progress of the restore behind the share, subject to the same restrictions as the share itself.
+pub async fn progress(
+    Path(token): Path<ShareToken>,
+    Extension(manager): Extension<RestoreManager>,
+    jar: CookieJar,
+) -> Result<Response> {
+    let share = manager.share(token).await?;
+
+    if let Err(violation) = check_progress_access(&manager, &share, &jar) {
+        return Ok(violation.into_response());
+    }
+
+    Ok(manager.progress(share.restore)?.into_response())
+}
+
+/// Checks whether the share permits the request, returning whether it resumes a download which has been counted already.
+///
+/// Only requests for a range past the beginning which present the ticket handed out along with a counted
+/// download are resumes, every other download has to be permitted by the download limit.
+async fn check_access(
+    manager: &RestoreManager,
+    share: &Share,
+    password: Option<&str>,
+    is_download: bool,
+    headers: &HeaderMap,
+    jar: &CookieJar,
+) -> std::result::Result<bool, PolicyViolation> {
+    let is_resume = is_download
+        && !is_initial_request(headers)
+        && jar
+            .get(RESUME_COOKIE_KEY)
+            .is_some_and(|ticket| manager.verify_resume_ticket(share.token, ticket.value()));
+
+    if is_resume {
+        share.check_resume(password).await?;
+    } else {
+        share.check(password).await?;
+    }
+
+    Ok(is_resume)
+}
+
+/// Checks whether the share permits following the progress of its restore. Progress is followed through
+/// plain `GET` requests, hence the password is vouched for by the ticket handed out once it was verified.
+fn check_progress_access(
+    manager: &RestoreManager,
+    share: &Share,
+    jar: &CookieJar,
+) -> std::result::Result<(), PolicyViolation> {
+    let is_unlocked = jar
+        .get(ACCESS_COOKIE_KEY)
+        .is_some_and(|ticket| manager.verify_access_ticket(share.token, ticket.value()));
+
+    share.check_progress(is_unlocked)
+}
+
+/// Cookie holding a ticket for the share, which is only sent along with requests concerning the share.
+fn ticket_cookie(
+    params: &CookieParameters,
+    token: ShareToken,
+    key: &str,
+    ticket: String,
+) -> Cookie<'static> {
+    let params = CookieParameters {
+        lifetime: TICKET_LIFETIME,
+        ..params.clone()
+    };
+    let mut cookie = params.cookie(key.to_string(), ticket, format!("/share/{token}"));
+
+    // Links to shares are usually followed from elsewhere
+    cookie.set_same_site(SameSite::Lax);
+    cookie
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use crate::restic::restore::{RestoreId, SharePolicy};
+    use axum::http::{header, HeaderValue};
+    use chrono::Utc;
+
+    async fn exhausted_share() -> Share {
+        let mut share = Share::new(RestoreId::try_from("01".repeat(32)).unwrap(), None);
+        share.policy = SharePolicy::new(None, Some(1), None).await;
+        share.downloads = 1;
+        share
+    }
+
+    fn range(range: &'static str) -> HeaderMap {
+        HeaderMap::from_iter([(header::RANGE, HeaderValue::from_static(range))])
+    }
+
+    #[tokio::test]
+    async fn count_suffix_ranges_without_ticket() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = RestoreManager::for_tests(root.path());
+        let share = exhausted_share().await;
+
+        let headers = range("bytes=-100");
+        let jar = CookieJar::new();
+
+        let checked = check_access(&manager, &share, None, true, &headers, &jar);
+        assert_eq!(checked.await, Err(PolicyViolation::Exhausted));
+    }
+
+    #[tokio::test]
+    async fn count_resumes_without_ticket() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = RestoreManager::for_tests(root.path());
+        let share = exhausted_share().await;
+        let headers = range("bytes=1-");
+        let jar = CookieJar::new();
+
+        let checked = check_access(&manager, &share, None, true, &headers, &jar);
+        assert_eq!(checked.await, Err(PolicyViolation::Exhausted));
+
+        // Tickets only hold for the share they have been handed out for
+        let other = manager.resume_ticket(ShareToken::new());
+        let jar = jar.add(Cookie::new(RESUME_COOKIE_KEY, other));
+
+        let checked = check_access(&manager, &share, None, true, &headers, &jar);
+        assert_eq!(checked.await, Err(PolicyViolation::Exhausted));
+    }
+
+    #[tokio::test]
+    async fn resume_counted_downloads_with_ticket() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = RestoreManager::for_tests(root.path());
+        let share = exhausted_share().await;
+        let ticket = manager.resume_ticket(share.token);
+        let jar = CookieJar::new().add(Cookie::new(RESUME_COOKIE_KEY, ticket));
+
+        let headers = range("bytes=1-");
+        let checked = check_access(&manager, &share, None, true, &headers, &jar);
+        assert_eq!(checked.await, Ok(true));
+
+        // Starting over is another download
+        let headers = range("bytes=0-");
+        let checked = check_access(&manager, &share, None, true, &headers, &jar);
+        assert_eq!(checked.await, Err(PolicyViolation::Exhausted));
+    }
+
+    #[tokio::test]
+    async fn hide_progress_of_locked_shares() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = RestoreManager::for_tests(root.path());
+        let mut share = Share::new(RestoreId::try_from("01".repeat(32)).unwrap(), None);
+        share.policy = SharePolicy::new(None, None, Some(String::from("secret"))).await;
+
+        let jar = CookieJar::new();
+        let checked = check_progress_access(&manager, &share, &jar);
+        assert_eq!(checked, Err(PolicyViolation::PasswordRequired));
+
+        let ticket = manager.access_ticket(share.token);
+        let jar = jar.add(Cookie::new(ACCESS_COOKIE_KEY, ticket));
+        assert_eq!(check_progress_access(&manager, &share, &jar), Ok(()));
+
+        share.policy = share.policy.with_limits(Some(Utc::now()), None);
+        let checked = check_progress_access(&manager, &share, &jar);
+        assert_eq!(checked, Err(PolicyViolation::Expired));
+    }
+}
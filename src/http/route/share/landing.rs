@@ -0,0 +1,99 @@
+use crate::{
+    helper::filters,
+    restic::restore::{Restore, RestoreContent, Share},
+};
+use askama::Template;
+use chrono::{DateTime, Local};
+
+/// Tells recipients of a share what they are about to download.
+#[derive(Template)]
+#[template(path = "share/landing.html")]
+pub struct LandingPage {
+    name: String,
+    content: RestoreContent,
+    hash: Option<String>,
+    expires_at: Option<DateTime<Local>>,
+    message: Option<String>,
+    download_url: String,
+    checksum_url: String,
+    password: Option<String>,
+}
+
+impl LandingPage {
+    pub fn new(share: &Share, restore: &Restore, password: Option<String>) -> Self {
+        let hash = restore.hash().map(|hash| hash.to_hex().to_string());
+
+        // Whichever comes first, the link or the restore itself expiring
+        let expires_at = match (share.policy.expires_at, restore.expires_at) {
+            (Some(link), Some(restore)) => Some(link.min(restore)),
+            (link, restore) => link.or(restore),
+        };
+
+        Self {
+            name: restore.file_name(),
+            content: restore.content,
+            hash,
+            expires_at: expires_at.map(Into::into),
+            message: share.message.clone(),
+            download_url: format!("/share/{}?download", share.token),
+            checksum_url: format!("/share/{}?checksum", share.token),
+            password,
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use crate::restic::restore::{
+        progress::ProgressTracker, RestoreFilter, RestoreId, RestoreState, SharePolicy,
+    };
+    use chrono::{TimeDelta, Utc};
+
+    fn restore(expires_at: Option<DateTime<Utc>>) -> Restore {
+        Restore {
+            id: RestoreId::try_from("01".repeat(32)).unwrap(),
+            state: RestoreState::InProgress(ProgressTracker::new().handle()),
+            source: "/home/user/report.pdf".into(),
+            content: RestoreContent::File { size: 2048 },
+            layout: None,
+            filter: RestoreFilter::default(),
+            base_snapshot: None,
+            manifest: false,
+            repository: None,
+            snapshot: None,
+            expires_at,
+            owner: None,
+        }
+    }
+
+    #[test]
+    fn describe_the_shared_download() {
+        let restore = restore(None);
+        let mut share = Share::new(restore.id, None);
+        share.message = Some(String::from("Numbers for Q3"));
+
+        let page = LandingPage::new(&share, &restore, None).render().unwrap();
+
+        assert!(page.contains("report.pdf"));
+        assert!(page.contains("Numbers for Q3"));
+        assert!(page.contains(&format!("/share/{}?download", share.token)));
+        assert!(page.contains("Available once the download has been prepared"));
+    }
+
+    #[tokio::test]
+    async fn expire_with_whichever_comes_first() {
+        let link = Utc::now() + TimeDelta::days(1);
+        let restore = restore(Some(link + TimeDelta::days(2)));
+        let mut share = Share::new(restore.id, None);
+        share.policy = SharePolicy::new(Some(link), None, None).await;
+
+        let page = LandingPage::new(&share, &restore, None);
+
+        assert_eq!(page.expires_at, Some(link.into()));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::restic::restore::PolicyViolation;
+use askama::Template;
+use askama_axum::{IntoResponse, Response};
+use axum::http::StatusCode;
+
+#[derive(Template)]
+#[template(path = "share/unavailable.html")]
+pub struct UnavailablePage {
+    reason: PolicyViolation,
+}
+
+#[derive(Template)]
+#[template(path = "share/password.html")]
+pub struct PasswordPage {
+    invalid: bool,
+}
+
+impl UnavailablePage {
+    pub fn title(&self) -> &str {
+        "Link unavailable"
+    }
+}
+
+impl PasswordPage {
+    pub fn title(&self) -> &str {
+        "Password required"
+    }
+}
+
+impl IntoResponse for PolicyViolation {
+    fn into_response(self) -> Response {
+        match self {
+            PolicyViolation::Revoked | PolicyViolation::Expired | PolicyViolation::Exhausted => {
+                (StatusCode::GONE, UnavailablePage { reason: self }).into_response()
+            }
+            PolicyViolation::PasswordRequired => {
+                (StatusCode::UNAUTHORIZED, PasswordPage { invalid: false }).into_response()
+            }
+            PolicyViolation::PasswordInvalid => {
+                (StatusCode::UNAUTHORIZED, PasswordPage { invalid: true }).into_response()
+            }
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use clap::Parser;
-use error::Result;
+use error::{Error, Result};
 
 mod args;
+mod crypto;
 mod error;
 mod helper;
 mod http;
@@ -1,4 +1,4 @@
-use crate::{restic::repository::Repository, Result};
+use crate::{http::SessionStore, restic::repository::Repository, Result};
 use argon2::{password_hash::PasswordHashString, Argon2, PasswordVerifier as _};
 use hex::FromHex;
 use rand::{thread_rng, Rng};
@@ -28,26 +28,36 @@ pub struct RepositoryCache {
 
     locations: HashMap<String, RepositoryLocation>,
     entries: Arc<Mutex<HashMap<SessionId, CachedRepository>>>,
+    store: Option<SessionStore>,
 }
 
 struct CachedRepository {
     handle: AbortHandle,
     repository: Repository,
+    owner: Option<String>,
 }
 
 impl RepositoryCache {
     pub fn new(
         locations: impl IntoIterator<Item = RepositoryLocation>,
         lifetime: Duration,
+        store: Option<SessionStore>,
     ) -> Self {
         Self {
             lifetime,
             locations: locations.into_iter().map(|l| (l.name.clone(), l)).collect(),
             entries: Arc::new(Mutex::new(HashMap::new())),
+            store,
         }
     }
 
-    pub fn open(&self, name: impl AsRef<str>, password: String) -> Result<(Repository, SessionId)> {
+    /// Unlocks a repository on behalf of the site session identified by `owner`.
+    pub fn open(
+        &self,
+        name: impl AsRef<str>,
+        password: String,
+        owner: SessionId,
+    ) -> Result<(Repository, SessionId)> {
         let name = name.as_ref().to_string();
 
         let location = self.locations.get(&name).cloned().ok_or(io::Error::new(
@@ -60,9 +70,15 @@ impl RepositoryCache {
             .map_err(|_| io::Error::new(io::ErrorKind::PermissionDenied, "invalid password"))?;
 
         let id = SessionId::new();
-        let repo = Repository::open(location.name, location.path, password)?;
+        let repo = Repository::open(location.name, location.path, &password)?;
 
-        self.insert(id, repo.clone());
+        let owner = owner.handle();
+
+        if let Some(store) = &self.store {
+            store.insert_repository(id, &name, &password, &owner, self.lifetime);
+        }
+
+        self.insert(id, repo.clone(), Some(owner), self.lifetime);
 
         Ok((repo, id))
     }
@@ -71,41 +87,124 @@ impl RepositoryCache {
         let mut cache = self.entries.lock().expect("repo cache poisoned");
 
         cache.entry(session).and_modify(|entry| {
-            let new_handle = self.spawn_lifetime_task(session);
+            let new_handle = self.spawn_lifetime_task(session, self.lifetime);
             let old_handle = mem::replace(&mut entry.handle, new_handle);
             old_handle.abort();
         });
 
-        cache.get(&session).map(|entry| entry.repository.clone())
+        let repository = cache.get(&session).map(|entry| entry.repository.clone());
+        drop(cache);
+
+        match (repository, &self.store) {
+            (Some(repository), Some(store)) => {
+                store.touch_repository(session, self.lifetime);
+                Some(repository)
+            }
+            (None, Some(_)) => self.reopen(session),
+            (repository, None) => repository,
+        }
+    }
+
+    /// Re-opens a repository whose session has been persisted before the server restarted.
+    fn reopen(&self, session: SessionId) -> Option<Repository> {
+        let restored = self.store.as_ref()?.repository(session)?;
+        let location = self.locations.get(&restored.name).cloned()?;
+
+        match Repository::open(location.name, location.path, restored.password) {
+            Ok(repository) => {
+                self.insert(
+                    session,
+                    repository.clone(),
+                    restored.owner,
+                    restored.remaining,
+                );
+                Some(repository)
+            }
+            Err(err) => {
+                eprintln!("Failed to re-open persisted repository session: {err}");
+                None
+            }
+        }
+    }
+
+    /// Locks the repository again by evicting the session.
+    pub fn remove(&self, id: SessionId) {
+        self.entries
+            .lock()
+            .expect("repo cache poisoned")
+            .remove(&id);
+
+        if let Some(store) = &self.store {
+            store.remove_repository(id);
+        }
+    }
+
+    /// Evicts all repository sessions which have been unlocked by the given site session.
+    pub fn remove_owned_by(&self, owner: &str) {
+        self.entries
+            .lock()
+            .expect("repo cache poisoned")
+            .retain(|_, entry| entry.owner.as_deref() != Some(owner));
+
+        if let Some(store) = &self.store {
+            store.remove_repositories_owned_by(owner);
+        }
     }
 
-    fn insert(&self, id: SessionId, repository: Repository) {
+    /// Names of the repositories unlocked by the given site session.
+    pub fn unlocked_by(&self, owner: &str) -> Vec<String> {
+        let mut names = self
+            .entries
+            .lock()
+            .expect("repo cache poisoned")
+            .values()
+            .filter(|entry| entry.owner.as_deref() == Some(owner))
+            .map(|entry| entry.repository.name().to_string())
+            .collect::<Vec<_>>();
+
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn insert(
+        &self,
+        id: SessionId,
+        repository: Repository,
+        owner: Option<String>,
+        lifetime: Duration,
+    ) {
         self.entries
             .lock()
             .expect("repo cache poisoned")
             .entry(id)
             .and_modify(|entry| {
-                let new_handle = self.spawn_lifetime_task(id);
+                let new_handle = self.spawn_lifetime_task(id, lifetime);
                 let old_handle = mem::replace(&mut entry.handle, new_handle);
                 old_handle.abort();
             })
             .or_insert_with(|| CachedRepository {
                 repository,
-                handle: self.spawn_lifetime_task(id),
+                owner,
+                handle: self.spawn_lifetime_task(id, lifetime),
             });
     }
 
-    fn spawn_lifetime_task(&self, id: SessionId) -> AbortHandle {
-        tokio::spawn(self.clone().purge_entry(id)).abort_handle()
+    fn spawn_lifetime_task(&self, id: SessionId, lifetime: Duration) -> AbortHandle {
+        tokio::spawn(self.clone().purge_entry(id, lifetime)).abort_handle()
     }
 
-    async fn purge_entry(self, id: SessionId) {
-        sleep(self.lifetime).await;
+    async fn purge_entry(self, id: SessionId, lifetime: Duration) {
+        sleep(lifetime).await;
 
         self.entries
             .lock()
             .expect("repo cache poisoned")
             .remove(&id);
+
+        if let Some(store) = &self.store {
+            store.remove_repository(id);
+        }
     }
 }
 
@@ -116,6 +215,15 @@ impl SessionId {
         thread_rng().fill(&mut session_id);
         Self(session_id)
     }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Public identifier of the session which can be shown to users without leaking the ID itself.
+    pub fn handle(&self) -> String {
+        blake3::hash(&self.0).to_hex().to_string()
+    }
 }
 
 impl ToString for SessionId {
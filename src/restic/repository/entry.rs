@@ -17,7 +17,7 @@ pub enum EntryKind {
 }
 
 impl Entry {
-    pub(super) fn new(node: Node, path: PathBuf) -> Result<Self> {
+    pub(in crate::restic) fn new(node: Node, path: PathBuf) -> Result<Self> {
         Ok(Self {
             path,
             kind: node.node_type.try_into()?,
@@ -6,7 +6,7 @@ use rustic_core::{
 };
 use std::{
     io, iter,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
 // TODO Remove this if not needed
@@ -99,6 +99,46 @@ impl Snapshot {
             .repo
             .node_from_snapshot_and_path(&self.snapshot_file, &path.as_ref().to_string_lossy())?)
     }
+
+    /// Like [`Self::node`], but tells a path which does not exist in the snapshot apart from failing to read it.
+    pub(in crate::restic) fn find_node(&self, path: impl AsRef<Path>) -> Result<Option<Node>> {
+        let path = path.as_ref();
+        let mut subtree = Some(self.snapshot_file.tree);
+        let mut found = None;
+
+        for component in path.components() {
+            let name = match component {
+                Component::Normal(name) => name,
+                Component::RootDir | Component::CurDir => continue,
+                // Rejected with an appropriate error
+                Component::Prefix(_) | Component::ParentDir => return self.node(path).map(Some),
+            };
+
+            // Entries can only be found within directories
+            let Some(tree) = subtree else {
+                return Ok(None);
+            };
+
+            let Some(node) = self
+                .repo
+                .get_tree(&tree)?
+                .nodes
+                .into_iter()
+                .find(|node| node.name() == name)
+            else {
+                return Ok(None);
+            };
+
+            subtree = node.subtree;
+            found = Some(node);
+        }
+
+        match found {
+            Some(node) => Ok(Some(node)),
+            // The root directory of the snapshot
+            None => self.node(path).map(Some),
+        }
+    }
 }
 
 #[derive(Clone)]
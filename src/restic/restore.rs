@@ -1,30 +1,125 @@
+use crate::{crypto::StreamCipher, restic::repository::Snapshot};
+use chrono::{DateTime, Utc};
 use progress::ProgressReceiver;
-use std::{fs::File, path::PathBuf};
+use std::path::{Path, PathBuf};
+use storage::StoredData;
 
+mod cipher;
 mod content;
 mod destination;
+mod diff;
+mod failure;
+mod filter;
 mod hash;
 mod id;
+mod layout;
 mod manager;
 mod plan;
+mod policy;
+mod share;
+mod stream;
 
 pub mod progress;
+pub mod storage;
 
+pub use cipher::DecryptReader;
 pub use content::RestoreContent;
-pub use id::RestoreId;
-pub use manager::RestoreManager;
+pub use failure::{FailureKind, RestoreFailure};
+pub use filter::RestoreFilter;
+pub use hash::digest_header;
+pub use id::{ContentId, RestoreId};
+pub use layout::ArchiveLayout;
+pub use manager::{
+    ConcurrencyLimits, RestoreManager, RestoreSummary, RetentionPolicy, StorageLimits,
+    TICKET_LIFETIME,
+};
+pub use policy::{PolicyViolation, SharePolicy};
+pub use share::{Share, ShareToken};
+pub use stream::DataReceiver;
 
 pub enum RestoreState {
     InProgress(ProgressReceiver),
     Available {
-        file: File,
+        data: Box<dyn StoredData>,
         hash: blake3::Hash,
-        content: RestoreContent,
+        /// Cipher to decrypt the data with, absent for restores stored in plain text
+        cipher: Option<StreamCipher>,
+        /// URL from which the data can be downloaded straight from storage
+        presigned: Option<String>,
     },
+    Failed(RestoreFailure),
+}
+
+/// Choices made when requesting a restore, which are part of its identity.
+#[derive(Default)]
+pub struct RestoreOptions {
+    /// Layout of an archive, the one configured for the server if absent
+    pub layout: Option<ArchiveLayout>,
+    pub filter: RestoreFilter,
+    /// Earlier snapshot of the same repository, restricting the restore to what changed since
+    pub base: Option<Snapshot>,
+    /// Whether archives contain a checksum manifest and a description of the snapshot
+    pub manifest: bool,
 }
 
 pub struct Restore {
     pub id: RestoreId,
     pub state: RestoreState,
     pub source: PathBuf,
+    pub content: RestoreContent,
+    /// Absent for restores which predate choosing a layout
+    pub layout: Option<ArchiveLayout>,
+    pub filter: RestoreFilter,
+    /// Snapshot relative to which only changes have been restored
+    pub base_snapshot: Option<String>,
+    /// Whether the archive contains a checksum manifest and a description of the snapshot
+    pub manifest: bool,
+    /// Origin of the restore from which it can be retried, absent for restores which predate recording it
+    pub repository: Option<String>,
+    pub snapshot: Option<String>,
+    /// Absent for pinned restores which never expire
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Handle of the session which requested the restore
+    pub owner: Option<String>,
+}
+
+impl Restore {
+    /// Name under which the restored data is offered for download.
+    pub fn file_name(&self) -> String {
+        Self::file_name_for(&self.source, &self.content)
+    }
+
+    /// Whether the session may cancel or retry the restore.
+    /// Restores which predate recording their owner can be retried by anyone, as before.
+    pub fn is_owned_by(&self, handle: &str) -> bool {
+        self.owner.as_deref().is_none_or(|owner| owner == handle)
+    }
+
+    /// Hash of the data as it is downloaded, available once the restore is complete.
+    pub fn hash(&self) -> Option<blake3::Hash> {
+        match &self.state {
+            RestoreState::Available { hash, .. } => Some(*hash),
+            RestoreState::InProgress(_) | RestoreState::Failed(_) => None,
+        }
+    }
+
+    /// Checksum file accompanying the download as its name and contents, see [`hash::checksum_file`].
+    pub fn checksum_file(&self) -> Option<(String, String)> {
+        let name = self.file_name();
+        let contents = hash::checksum_file(&self.hash()?, &name);
+
+        Some((format!("{name}.b3"), contents))
+    }
+
+    fn file_name_for(source: &Path, content: &RestoreContent) -> String {
+        let source_name = source
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| String::from("restore"));
+
+        match content {
+            RestoreContent::File { .. } => source_name,
+            RestoreContent::Archive { .. } => format!("{source_name}.zip"),
+        }
+    }
 }
@@ -0,0 +1,115 @@
+use crate::crypto::StreamCipher;
+use std::{
+    io::{self, Read, Write},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Encrypts everything written to it. Deliberately not seekable, as overwriting
+/// data which has already been written would reuse the keystream.
+/// Without a cipher, data is passed through unchanged.
+pub struct EncryptWriter<W: Write> {
+    writer: W,
+    cipher: Option<StreamCipher>,
+    position: u64,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    pub fn new(writer: W, cipher: Option<StreamCipher>) -> Self {
+        Self {
+            writer,
+            cipher,
+            position: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(cipher) = &mut self.cipher else {
+            let written = self.writer.write(buf)?;
+            self.position += written as u64;
+            return Ok(written);
+        };
+
+        self.buffer.clear();
+        self.buffer.extend_from_slice(buf);
+
+        cipher.seek(self.position);
+        cipher.apply(&mut self.buffer);
+
+        let written = self.writer.write(&self.buffer)?;
+        self.position += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Decrypts data read from storage. Restores created before encryption
+/// was introduced have no cipher and are passed through unchanged.
+pub struct DecryptReader<R> {
+    reader: R,
+    cipher: Option<StreamCipher>,
+    position: u64,
+}
+
+impl<R> DecryptReader<R> {
+    /// Wraps a reader which starts at the given position within the stored data.
+    pub fn new(reader: R, cipher: Option<StreamCipher>, position: u64) -> Self {
+        Self {
+            reader,
+            cipher,
+            position,
+        }
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+
+        if let Some(cipher) = &mut self.cipher {
+            cipher.seek(self.position);
+            cipher.apply(&mut buf[..read]);
+        }
+
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for DecryptReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let offset = buf.filled().len();
+
+        ready!(Pin::new(&mut this.reader).poll_read(cx, buf))?;
+
+        let data = &mut buf.filled_mut()[offset..];
+
+        if let Some(cipher) = &mut this.cipher {
+            cipher.seek(this.position);
+            cipher.apply(data);
+        }
+
+        this.position += data.len() as u64;
+
+        Poll::Ready(Ok(()))
+    }
+}
@@ -89,6 +89,8 @@ impl From<RestoreContent> for Progress {
                 files: None,
                 directories: None,
                 status: Status::Restoring,
+                errors: 0,
+                failure: None,
             },
             RestoreContent::Archive {
                 size,
@@ -99,6 +101,8 @@ impl From<RestoreContent> for Progress {
                 files: Some(ProgressVariable::new(files)),
                 directories: Some(ProgressVariable::new(directories)),
                 status: Status::Restoring,
+                errors: 0,
+                failure: None,
             },
         }
     }
@@ -4,12 +4,30 @@ use std::{
 };
 
 mod archive;
+mod checksum;
 mod file;
 
 pub use archive::ArchiveDestination;
 pub use file::FileDestination;
 
 pub trait RestoreDestination {
-    fn add_file(&mut self, path: PathBuf) -> io::Result<impl Write>;
+    fn add_file(&mut self, path: PathBuf) -> io::Result<impl FileWriter>;
     fn add_dir(&mut self, path: PathBuf) -> io::Result<()>;
+    /// Adds a text file describing the restore itself rather than any entry of the snapshot.
+    fn add_report(&mut self, name: &str, contents: &str) -> io::Result<()>;
+    /// Completes the restore once all entries and reports have been added.
+    fn finish(self) -> io::Result<()>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
+    fn is_cancelled(&self) -> bool;
+}
+
+/// Receives the data of a single file.
+pub trait FileWriter: Write {
+    /// Completes the file once all of its data has been written.
+    /// Files which are abandoned partway, e.g. because they could not be read, are never finished.
+    fn finish(self) -> io::Result<()>;
 }
@@ -1,46 +1,76 @@
-use super::RestoreDestination;
+use super::{
+    checksum::{ChecksumWriter, MANIFEST_NAME},
+    FileWriter, RestoreDestination,
+};
 use crate::restic::restore::progress::{
     ProgressTracker,
     ProgressUnit::{Directory, File},
     ProgressWriter,
 };
 use std::{
-    io::{self, Seek, Write},
-    path::{Path, PathBuf},
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+};
+use zip::{
+    write::{SimpleFileOptions, StreamWriter},
+    CompressionMethod, ZipWriter,
 };
-use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
 
-pub struct ArchiveDestination<'p, W: Write + Seek> {
-    archive: ZipWriter<W>,
+/// Name of the file describing the snapshot the archive has been restored from
+const INFO_NAME: &str = "SNAPSHOT.txt";
+
+/// Writes a zip archive front to back, so that it can be downloaded while it is being written.
+pub struct ArchiveDestination<'p, W: Write> {
+    archive: ZipWriter<StreamWriter<W>>,
     progress: &'p mut ProgressTracker,
     path_base: PathBuf,
+    /// Folder within the archive in which all entries are placed
+    folder: PathBuf,
+    /// Checksums of the files written so far and a description of the snapshot, if the archive is to describe itself
+    manifest: Option<(String, String)>,
 }
 
-impl<'p, W: Write + Seek> ArchiveDestination<'p, W> {
+impl<'p, W: Write> ArchiveDestination<'p, W> {
     pub fn new(
         writer: W,
         progress: &'p mut ProgressTracker,
         path_base: PathBuf,
+        folder: PathBuf,
     ) -> io::Result<Self> {
-        let mut archive = ZipWriter::new(writer);
+        let mut archive = ZipWriter::new_stream(writer);
         archive.set_comment("ResticDL Restore");
 
         Ok(Self {
             archive,
             progress,
             path_base,
+            folder,
+            manifest: None,
         })
     }
 
-    fn path_suffix(&self, path: &Path) -> PathBuf {
+    /// Adds a checksum manifest and the given description of the snapshot next to the entries.
+    pub fn with_manifest(mut self, info: String) -> Self {
+        self.manifest = Some((String::new(), info));
+        self
+    }
+
+    /// Path of the entry relative to the folder, as it is stored within the archive.
+    fn entry_path(&self, path: &Path) -> PathBuf {
         path.strip_prefix(&self.path_base)
-            .unwrap_or(&path)
-            .to_path_buf()
+            .unwrap_or(path)
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect()
+    }
+
+    fn path_suffix(&self, path: &Path) -> PathBuf {
+        self.folder.join(self.entry_path(path))
     }
 }
 
-impl<'p, W: Write + Seek> RestoreDestination for ArchiveDestination<'p, W> {
-    fn add_file(&mut self, path: PathBuf) -> io::Result<impl Write> {
+impl<'p, W: Write> RestoreDestination for ArchiveDestination<'p, W> {
+    fn add_file(&mut self, path: PathBuf) -> io::Result<impl FileWriter> {
         *self.progress += File;
 
         let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
@@ -51,7 +81,11 @@ impl<'p, W: Write + Seek> RestoreDestination for ArchiveDestination<'p, W> {
         self.archive
             .start_file_from_path(self.path_suffix(&path), options)?;
 
-        Ok(ProgressWriter::new(&mut self.archive, &mut self.progress))
+        let entry_path = self.entry_path(&path);
+        let manifest = self.manifest.as_mut().map(|(checksums, _)| checksums);
+        let writer = ProgressWriter::new(&mut self.archive, self.progress);
+
+        Ok(ChecksumWriter::new(writer, entry_path, manifest))
     }
 
     fn add_dir(&mut self, path: PathBuf) -> io::Result<()> {
@@ -61,4 +95,94 @@ impl<'p, W: Write + Seek> RestoreDestination for ArchiveDestination<'p, W> {
             .archive
             .add_directory_from_path(self.path_suffix(&path), SimpleFileOptions::default())?)
     }
+
+    fn add_report(&mut self, name: &str, contents: &str) -> io::Result<()> {
+        self.archive
+            .start_file_from_path(self.folder.join(name), SimpleFileOptions::default())?;
+        self.archive.write_all(contents.as_bytes())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        if let Some((checksums, info)) = self.manifest.take() {
+            self.add_report(INFO_NAME, &info)?;
+            self.add_report(MANIFEST_NAME, &checksums)?;
+        }
+
+        // Dropping the archive writes the central directory as well, but swallows any error doing so
+        self.archive.finish()?;
+
+        Ok(())
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.progress.is_cancelled()
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use crate::restic::restore::progress::{Progress, ProgressVariable};
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    #[test]
+    fn list_only_finished_files_in_manifest() {
+        let mut progress = ProgressTracker::new();
+        progress.set_state(Progress {
+            files: Some(ProgressVariable::new(2)),
+            ..Progress::default()
+        });
+
+        let mut data = Vec::new();
+        let mut destination =
+            ArchiveDestination::new(&mut data, &mut progress, "/src".into(), "src".into())
+                .unwrap()
+                .with_manifest(String::from("Snapshot"));
+
+        let mut complete = destination.add_file("/src/complete.txt".into()).unwrap();
+        complete.write_all(b"hello").unwrap();
+        complete.finish().unwrap();
+
+        // Entries are abandoned partway if their data can not be read from the repository
+        let mut failed = destination.add_file("/src/failed.txt".into()).unwrap();
+        failed.write_all(b"hel").unwrap();
+        drop(failed);
+
+        destination.finish().unwrap();
+
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        let mut manifest = String::new();
+        archive
+            .by_name("src/SHA256SUMS")
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .unwrap();
+
+        assert_eq!(
+            manifest,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  complete.txt\n"
+        );
+    }
+
+    #[test]
+    fn report_failing_to_complete_the_archive() {
+        let mut progress = ProgressTracker::new();
+        progress.set_state(Progress {
+            files: Some(ProgressVariable::new(1)),
+            ..Progress::default()
+        });
+
+        // Fits the entry, but not the central directory following it
+        let mut data = [0; 100];
+        let mut destination =
+            ArchiveDestination::new(&mut data[..], &mut progress, "/src".into(), "src".into())
+                .unwrap();
+
+        let mut file = destination.add_file("/src/file.txt".into()).unwrap();
+        file.write_all(b"hello").unwrap();
+        file.finish().unwrap();
+
+        assert!(destination.finish().is_err());
+    }
 }
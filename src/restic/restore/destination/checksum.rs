@@ -0,0 +1,60 @@
+use super::FileWriter;
+use sha2::{Digest, Sha256};
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// Name of the manifest listing the checksum of every file, as verified by `sha256sum -c`
+pub const MANIFEST_NAME: &str = "SHA256SUMS";
+
+/// Hashes the data written through it and adds the digest to the manifest once the file is finished.
+/// Passes the data through unchanged if there is no manifest.
+pub struct ChecksumWriter<'m, W: Write> {
+    writer: W,
+    hasher: Sha256,
+    path: PathBuf,
+    manifest: Option<&'m mut String>,
+}
+
+impl<'m, W: Write> ChecksumWriter<'m, W> {
+    pub fn new(writer: W, path: PathBuf, manifest: Option<&'m mut String>) -> Self {
+        Self {
+            writer,
+            hasher: Sha256::new(),
+            path,
+            manifest,
+        }
+    }
+}
+
+impl<'m, W: Write> Write for ChecksumWriter<'m, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+
+        if self.manifest.is_some() {
+            self.hasher.update(&buf[..written]);
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<'m, W: FileWriter> FileWriter for ChecksumWriter<'m, W> {
+    /// Adds the digest to the manifest, which leaves out files abandoned partway.
+    fn finish(self) -> io::Result<()> {
+        if let Some(manifest) = self.manifest {
+            manifest.push_str(&format!(
+                "{}  {}\n",
+                hex::encode(self.hasher.finalize()),
+                self.path.display()
+            ));
+        }
+
+        self.writer.finish()
+    }
+}
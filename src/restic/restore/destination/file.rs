@@ -1,4 +1,4 @@
-use super::RestoreDestination;
+use super::{FileWriter, RestoreDestination};
 use crate::restic::restore::progress::{ProgressTracker, ProgressWriter};
 use std::{
     io::{self, Write},
@@ -17,11 +17,29 @@ impl<'p, W: Write> FileDestination<'p, W> {
 }
 
 impl<'p, W: Write> RestoreDestination for FileDestination<'p, W> {
-    fn add_file(&mut self, _: PathBuf) -> io::Result<impl Write> {
+    fn add_file(&mut self, _: PathBuf) -> io::Result<impl FileWriter> {
         Ok(ProgressWriter::new(&mut self.writer, &mut self.progress))
     }
 
     fn add_dir(&mut self, _: PathBuf) -> io::Result<()> {
         unimplemented!("FileDestination does not support the creation of directories")
     }
+
+    /// Reports only accompany archives, a single file has no room for them.
+    fn add_report(&mut self, name: &str, _: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unable to add report `{name}` to the restore of a single file"),
+        ))
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.progress.is_cancelled()
+    }
+}
+
+impl<'p, W: Write> FileWriter for ProgressWriter<'p, W> {
+    fn finish(self) -> io::Result<()> {
+        Ok(())
+    }
 }
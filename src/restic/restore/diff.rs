@@ -0,0 +1,226 @@
+use super::{
+    plan::{archive_content, RestorePlan},
+    RestoreFilter,
+};
+use crate::{
+    restic::repository::{Entry, EntryKind, Snapshot},
+    Result,
+};
+use blake3::Hasher;
+use rustic_core::{repofile::Node, LsOptions};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    vec,
+};
+
+/// Name of the report listing entries which no longer exist in the newer snapshot
+const DELETION_REPORT_NAME: &str = "DELETED.txt";
+
+/// Contents of an entry, which tell whether it changed between snapshots.
+/// Directories are compared through their entries, changes to metadata alone are not considered.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Fingerprint {
+    Directory,
+    File(blake3::Hash),
+}
+
+/// Entries of a directory tree with their fingerprint, by their path relative to it.
+type Tree<T> = HashMap<PathBuf, (Fingerprint, T)>;
+
+/// Entries by their path relative to the tree they belong to.
+type Entries<T> = Vec<(PathBuf, T)>;
+
+impl Fingerprint {
+    fn of(node: &Node) -> Self {
+        if node.is_dir() {
+            return Self::Directory;
+        }
+
+        let mut hasher = Hasher::new();
+
+        for blob in node.content.iter().flatten() {
+            hasher.update(blob.to_hex().as_bytes());
+        }
+
+        Self::File(hasher.finalize())
+    }
+}
+
+impl Snapshot {
+    /// Plans the restore of the entries below the source directory which were added or modified since the base snapshot.
+    /// Entries which have been deleted since are listed in a report instead.
+    pub fn diff(
+        &self,
+        base: &Snapshot,
+        source: impl AsRef<Path>,
+        filter: &RestoreFilter,
+    ) -> Result<RestorePlan<'_, vec::IntoIter<Entry>>> {
+        let source = source.as_ref();
+        let target = self.entry(source)?;
+
+        if target.kind != EntryKind::Directory {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only directories can be compared between snapshots",
+            )
+            .into());
+        }
+
+        let matcher = filter.matcher()?;
+
+        // Everything counts as added if the directory did not exist yet
+        let previous = match base.find_node(source)? {
+            Some(node) if node.is_dir() => base.tree(source, &node)?,
+            _ => HashMap::new(),
+        };
+
+        let (changed, deleted) = compare(previous, self.tree(source, &self.node(source)?)?);
+
+        let mut entries = changed
+            .into_iter()
+            .filter(|(path, entry)| matcher.matches(entry, path))
+            .map(|(_, entry)| entry)
+            .collect::<Vec<_>>();
+
+        // Keeps parents ahead of their children, as the tree has been collected out of order
+        entries.sort_unstable_by(|l, r| l.path.cmp(&r.path));
+
+        let mut deleted = deleted
+            .into_iter()
+            .filter(|(path, entry)| matcher.matches(entry, path))
+            .map(|(_, entry)| entry.path)
+            .collect::<Vec<_>>();
+        deleted.sort_unstable();
+
+        let mut report = format!(
+            "The following entries exist in snapshot {} but no longer in snapshot {}.\n\n",
+            &base.id().as_str()[0..8],
+            &self.id().as_str()[0..8]
+        );
+
+        for path in deleted {
+            report.push_str(&format!("{}\n", path.display()));
+        }
+
+        Ok(RestorePlan {
+            snapshot: self,
+            content: archive_content(entries.iter().cloned()),
+            entries: entries.into_iter(),
+
+            source: target,
+            reports: vec![(DELETION_REPORT_NAME, report)],
+        })
+    }
+
+    /// Entries below the directory by their path relative to it, leaving out those which are neither files nor directories.
+    fn tree(&self, source: &Path, node: &Node) -> Result<Tree<Entry>> {
+        let options = LsOptions::default().recursive(true);
+        let mut tree = HashMap::new();
+
+        // Entries which can not be listed would otherwise count as deleted
+        for listed in self.repo.ls(node, &options)? {
+            let (path, node) = listed?;
+            let fingerprint = Fingerprint::of(&node);
+
+            if let Ok(entry) = Entry::new(node, source.join(&path)) {
+                tree.insert(path, (fingerprint, entry));
+            }
+        }
+
+        Ok(tree)
+    }
+}
+
+/// Splits the current tree into the entries which were added or modified since the previous tree,
+/// and returns the entries of the previous tree which have no counterpart in the current one alongside.
+fn compare<T>(mut previous: Tree<T>, current: Tree<T>) -> (Entries<T>, Entries<T>) {
+    let mut changed = Vec::new();
+
+    for (path, (fingerprint, entry)) in current {
+        let is_changed = previous
+            .remove(&path)
+            .is_none_or(|(previous, _)| previous != fingerprint);
+
+        if is_changed {
+            changed.push((path, entry));
+        }
+    }
+
+    // Whatever is left has no counterpart in the current tree
+    let deleted = previous
+        .into_iter()
+        .map(|(path, (_, entry))| (path, entry))
+        .collect();
+
+    (changed, deleted)
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn tree(entries: &[(&str, Fingerprint)]) -> Tree<String> {
+        entries
+            .iter()
+            .map(|(path, fingerprint)| (PathBuf::from(path), (*fingerprint, path.to_string())))
+            .collect()
+    }
+
+    fn file(contents: &str) -> Fingerprint {
+        Fingerprint::File(blake3::hash(contents.as_bytes()))
+    }
+
+    fn sorted(mut entries: Vec<(PathBuf, String)>) -> Vec<String> {
+        entries.sort_unstable();
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    #[test]
+    fn classify_added_modified_and_deleted_entries() {
+        let previous = tree(&[
+            ("docs", Fingerprint::Directory),
+            ("docs/kept.txt", file("kept")),
+            ("docs/modified.txt", file("before")),
+            ("docs/deleted.txt", file("gone")),
+            ("old", Fingerprint::Directory),
+        ]);
+        let current = tree(&[
+            ("docs", Fingerprint::Directory),
+            ("docs/kept.txt", file("kept")),
+            ("docs/modified.txt", file("after")),
+            ("docs/added.txt", file("new")),
+            ("new", Fingerprint::Directory),
+        ]);
+
+        let (changed, deleted) = compare(previous, current);
+
+        assert_eq!(
+            sorted(changed),
+            ["docs/added.txt", "docs/modified.txt", "new"]
+        );
+        assert_eq!(sorted(deleted), ["docs/deleted.txt", "old"]);
+    }
+
+    #[test]
+    fn treat_everything_as_added_without_previous_tree() {
+        let current = tree(&[("docs", Fingerprint::Directory), ("docs/a.txt", file("a"))]);
+
+        let (changed, deleted) = compare(HashMap::new(), current);
+
+        assert_eq!(sorted(changed), ["docs", "docs/a.txt"]);
+        assert!(deleted.is_empty());
+    }
+
+    #[test]
+    fn detect_files_replaced_by_directories() {
+        let previous = tree(&[("entry", file("file"))]);
+        let current = tree(&[("entry", Fingerprint::Directory)]);
+
+        let (changed, deleted) = compare(previous, current);
+
+        assert_eq!(sorted(changed), ["entry"]);
+        assert!(deleted.is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io, path::PathBuf};
+
+/// Broad cause of a failed restore, telling users whether retrying is likely to help.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureKind {
+    /// Data could not be read from the repository, e.g. because a pack is missing
+    Repository,
+    /// The restore location ran out of space
+    Storage,
+    /// Access to the repository or restore location was denied
+    Permission,
+    /// The server was stopped while the restore was running
+    Interrupted,
+    /// The stored data was found to be damaged after the restore completed
+    Corrupted,
+    Cancelled,
+    Other,
+}
+
+/// Explanation of why a restore could not be completed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredFailure")]
+pub struct RestoreFailure {
+    pub kind: FailureKind,
+    pub message: String,
+    /// Entry within the snapshot which was being restored when the failure occurred
+    pub path: Option<PathBuf>,
+}
+
+/// Failures used to be recorded as plain messages.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredFailure {
+    Structured {
+        kind: FailureKind,
+        message: String,
+        path: Option<PathBuf>,
+    },
+    Message(String),
+}
+
+impl FailureKind {
+    pub fn description(&self) -> &'static str {
+        match self {
+            FailureKind::Repository => "The repository could not be read",
+            FailureKind::Storage => "There is not enough space to store the restore",
+            FailureKind::Permission => "Access was denied",
+            FailureKind::Interrupted => "The server was restarted",
+            FailureKind::Corrupted => "The stored data has been damaged",
+            FailureKind::Cancelled => "The restore was cancelled",
+            FailureKind::Other => "An unexpected error occurred",
+        }
+    }
+}
+
+impl RestoreFailure {
+    pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            path: None,
+        }
+    }
+}
+
+impl From<&Error> for RestoreFailure {
+    fn from(error: &Error) -> Self {
+        let kind = match error.root() {
+            Error::BackendError(_) => FailureKind::Repository,
+            Error::InsufficientStorage(_) => FailureKind::Storage,
+            Error::Cancelled => FailureKind::Cancelled,
+            Error::IoError(err) => match err.kind() {
+                io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => FailureKind::Storage,
+                io::ErrorKind::PermissionDenied => FailureKind::Permission,
+                _ => FailureKind::Other,
+            },
+            Error::InvalidFilter(_) | Error::Entry { .. } => FailureKind::Other,
+        };
+
+        Self {
+            kind,
+            message: error.root().to_string(),
+            path: error.path().map(ToOwned::to_owned),
+        }
+    }
+}
+
+impl From<StoredFailure> for RestoreFailure {
+    fn from(failure: StoredFailure) -> Self {
+        match failure {
+            StoredFailure::Structured {
+                kind,
+                message,
+                path,
+            } => Self {
+                kind,
+                message,
+                path,
+            },
+            StoredFailure::Message(message) => Self::new(FailureKind::Other, message),
+        }
+    }
+}
+
+impl fmt::Display for RestoreFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path.display(), self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn read_plain_messages() {
+        let failure: RestoreFailure = serde_json::from_str(r#""Interrupted""#).unwrap();
+        assert_eq!(
+            failure,
+            RestoreFailure::new(FailureKind::Other, "Interrupted")
+        );
+    }
+
+    #[test]
+    fn classify_errors_within_entries() {
+        let error = Error::IoError(io::ErrorKind::PermissionDenied.into()).at("/etc/shadow");
+        let failure = RestoreFailure::from(&error);
+
+        assert_eq!(failure.kind, FailureKind::Permission);
+        assert_eq!(failure.path, Some(PathBuf::from("/etc/shadow")));
+    }
+}
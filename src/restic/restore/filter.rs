@@ -0,0 +1,183 @@
+use crate::{
+    restic::repository::{Entry, EntryKind},
+    Error, Result,
+};
+use blake3::Hasher;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Restricts which entries of a directory are restored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreFilter {
+    /// Gitignore-style patterns of the entries to restore, all entries are restored if there are none
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Gitignore-style patterns of the entries to leave out, even if they are included
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Files larger than this many bytes are left out
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+}
+
+/// Compiled form of a [`RestoreFilter`].
+pub(super) struct FilterMatcher {
+    include: Option<Gitignore>,
+    exclude: Option<Gitignore>,
+    max_file_size: Option<u64>,
+}
+
+impl RestoreFilter {
+    /// Creates a filter from patterns given one per line, ignoring blank lines and `#` comments like a `.gitignore`.
+    pub fn from_lines(include: &str, exclude: &str, max_file_size: Option<u64>) -> Self {
+        let patterns = |lines: &str| {
+            lines
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(ToOwned::to_owned)
+                .collect()
+        };
+
+        Self {
+            include: patterns(include),
+            exclude: patterns(exclude),
+            max_file_size,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.max_file_size.is_none()
+    }
+
+    /// Adds the filter to the identity of a restore, which is left as is if nothing is filtered.
+    pub(super) fn hash(&self, hasher: &mut Hasher) {
+        if self.is_empty() {
+            return;
+        }
+
+        hasher.update(b"filter");
+
+        for (kind, patterns) in [(b"include", &self.include), (b"exclude", &self.exclude)] {
+            for pattern in patterns {
+                hasher.update(kind);
+                hasher.update(pattern.as_bytes());
+                hasher.update(&[0]);
+            }
+        }
+
+        if let Some(size) = self.max_file_size {
+            hasher.update(b"max-file-size");
+            hasher.update(&size.to_le_bytes());
+        }
+    }
+
+    /// Compiles the patterns, failing if any of them is invalid.
+    pub(super) fn matcher(&self) -> Result<FilterMatcher> {
+        Ok(FilterMatcher {
+            include: Self::compile(&self.include)?,
+            exclude: Self::compile(&self.exclude)?,
+            max_file_size: self.max_file_size,
+        })
+    }
+
+    fn compile(patterns: &[String]) -> Result<Option<Gitignore>> {
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+
+        let mut builder = GitignoreBuilder::new("");
+
+        for pattern in patterns {
+            builder
+                .add_line(None, pattern)
+                .map_err(|err| Error::InvalidFilter(err.to_string()))?;
+        }
+
+        let matcher = builder
+            .build()
+            .map_err(|err| Error::InvalidFilter(err.to_string()))?;
+
+        Ok(Some(matcher))
+    }
+}
+
+impl FilterMatcher {
+    /// Whether the entry is restored, given its path relative to the restored directory.
+    /// Entries within a matching directory match as well, directories leading up to them are implied by the archive.
+    pub fn matches(&self, entry: &Entry, path: &Path) -> bool {
+        let is_dir = entry.kind == EntryKind::Directory;
+        let matched = |patterns: &Gitignore| {
+            patterns
+                .matched_path_or_any_parents(path, is_dir)
+                .is_ignore()
+        };
+
+        if self
+            .include
+            .as_ref()
+            .is_some_and(|include| !matched(include))
+        {
+            return false;
+        }
+
+        if self.exclude.as_ref().is_some_and(matched) {
+            return false;
+        }
+
+        !(entry.kind == EntryKind::File && self.max_file_size.is_some_and(|max| entry.size > max))
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(path: &str, kind: EntryKind, size: u64) -> Entry {
+        Entry {
+            path: PathBuf::from(path),
+            kind,
+            size,
+        }
+    }
+
+    fn restored(filter: &RestoreFilter, entries: &[Entry]) -> Vec<String> {
+        let matcher = filter.matcher().unwrap();
+
+        entries
+            .iter()
+            .filter(|entry| matcher.matches(entry, &entry.path))
+            .map(|entry| entry.path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn filter_like_gitignore() {
+        let entries = [
+            entry("app", EntryKind::Directory, 0),
+            entry("app/main.rs", EntryKind::File, 10),
+            entry("app/node_modules", EntryKind::Directory, 0),
+            entry("app/node_modules/lib.js", EntryKind::File, 10),
+            entry("docs/report.pdf", EntryKind::File, 10),
+            entry("vm/disk.qcow2", EntryKind::File, 10_000),
+        ];
+
+        let filter =
+            RestoreFilter::from_lines("", "# Dependencies\nnode_modules/\n\n*.qcow2", None);
+        assert_eq!(
+            restored(&filter, &entries),
+            ["app", "app/main.rs", "docs/report.pdf"]
+        );
+
+        let filter = RestoreFilter::from_lines("/app\n*.pdf", "node_modules/", Some(100));
+        assert_eq!(
+            restored(&filter, &entries),
+            ["app", "app/main.rs", "docs/report.pdf"]
+        );
+
+        let filter = RestoreFilter::from_lines("", "", Some(100));
+        assert_eq!(restored(&filter, &entries).len(), 5);
+    }
+}
@@ -1,40 +1,42 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
 use blake3::{Hash, Hasher};
-use std::io::{self, Seek, Write};
+use std::io::{self, Read};
 
-pub struct HashWriter<W: Write> {
-    writer: W,
-    hasher: Hasher,
+/// Hashes everything the reader yields, which should be the data exactly as it is handed out.
+pub fn hash_data(mut reader: impl Read) -> io::Result<Hash> {
+    let mut hasher = Hasher::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher.finalize())
 }
 
-impl<W: Write> HashWriter<W> {
-    pub fn new(writer: W) -> Self {
-        Self {
-            writer,
-            hasher: Hasher::new(),
-        }
-    }
+/// Value of a `Repr-Digest` or `Digest` header carrying the hash, e.g. `blake3=:<base64>:`.
+pub fn digest_header(hash: &Hash, structured: bool) -> String {
+    let encoded = STANDARD.encode(hash.as_bytes());
 
-    pub fn finalize(self) -> (Hash, W) {
-        (self.hasher.finalize(), self.writer)
+    if structured {
+        format!("blake3=:{encoded}:")
+    } else {
+        format!("blake3={encoded}")
     }
 }
 
-impl<W: Write> Write for HashWriter<W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.hasher.update(buf);
-        self.writer.write(buf)
-    }
-
-    fn flush(&mut self) -> io::Result<()> {
-        self.writer.flush()
-    }
+/// Checksum file in the format of `b3sum`, which can be checked using `b3sum --check`.
+pub fn checksum_file(hash: &Hash, file_name: &str) -> String {
+    format!("{}  {file_name}\n", hash.to_hex())
 }
 
-impl<W> Seek for HashWriter<W>
-where
-    W: Write + Seek,
-{
-    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
-        self.writer.seek(pos)
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn produce_b3sum_checksums() {
+        let hash = hash_data(&b"abc"[..]).unwrap();
+        let expected = blake3::hash(b"abc");
+
+        assert_eq!(
+            checksum_file(&hash, "abc.txt"),
+            format!("{}  abc.txt\n", expected.to_hex())
+        );
     }
 }
@@ -1,22 +1,142 @@
+use super::{ArchiveLayout, RestoreFilter};
 use crate::restic::repository::Snapshot;
 use blake3::{Hash, Hasher, HexError};
 use serde::{Deserialize, Serialize};
-use std::{fmt, ops::Deref, path::Path};
+use std::{fmt, io, ops::Deref, path::Path};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 #[serde(into = "String", try_from = "String")]
 pub struct RestoreId(Hash);
 
 impl RestoreId {
-    pub fn new(snapshot: &Snapshot, source: impl AsRef<Path>) -> Self {
+    pub fn new(
+        snapshot: &Snapshot,
+        source: impl AsRef<Path>,
+        layout: ArchiveLayout,
+        filter: &RestoreFilter,
+        base: Option<&Snapshot>,
+        manifest: bool,
+    ) -> Self {
         let mut hasher = Hasher::new();
         hasher.update(snapshot.repo().id().as_bytes());
         hasher.update(snapshot.id().as_bytes());
         hasher.update(source.as_ref().as_os_str().as_encoded_bytes());
+
+        // Restores from before layouts could be chosen keep their identity
+        if layout != ArchiveLayout::Relative {
+            hasher.update(layout.as_str().as_bytes());
+        }
+
+        filter.hash(&mut hasher);
+
+        if let Some(base) = base {
+            hasher.update(b"diff");
+            hasher.update(base.id().as_bytes());
+        }
+
+        if manifest {
+            hasher.update(b"manifest");
+        }
+
         Self(hasher.finalize())
     }
 }
 
+/// Identity of the data restored from a source, shared by all snapshots in which the source is unchanged.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct ContentId(Hash);
+
+impl ContentId {
+    /// Derives the identity from the blobs of a file or the subtree of a directory.
+    /// Archives additionally depend on their filter and on the location of the source or the snapshot time, if their layout includes it.
+    /// Archives of changes additionally depend on the directory in the base snapshot, and those describing themselves on the snapshot.
+    pub fn new(
+        snapshot: &Snapshot,
+        source: impl AsRef<Path>,
+        layout: ArchiveLayout,
+        filter: &RestoreFilter,
+        base: Option<&Snapshot>,
+        manifest: bool,
+    ) -> crate::Result<Self> {
+        let node = snapshot.node(&source)?;
+        let mut hasher = Hasher::new();
+
+        // Data is not shared across repositories to not reveal their contents to each other
+        hasher.update(snapshot.repo().id().as_bytes());
+
+        if node.is_dir() {
+            let subtree = node.subtree.ok_or(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "directory without subtree",
+            ))?;
+
+            hasher.update(b"tree");
+            hasher.update(subtree.to_hex().as_bytes());
+
+            match layout {
+                ArchiveLayout::Relative => {}
+                ArchiveLayout::Full => {
+                    hasher.update(source.as_ref().as_os_str().as_encoded_bytes());
+                }
+                ArchiveLayout::Dated => {
+                    hasher.update(b"dated");
+                    hasher.update(layout.folder(snapshot).as_os_str().as_encoded_bytes());
+                }
+            }
+
+            filter.hash(&mut hasher);
+
+            if let Some(base) = base {
+                hasher.update(b"diff");
+
+                match base.node(&source) {
+                    Ok(node) => match node.subtree {
+                        Some(subtree) => hasher.update(subtree.to_hex().as_bytes()),
+                        None => hasher.update(b"file"),
+                    },
+                    Err(_) => hasher.update(b"absent"),
+                };
+            }
+
+            // The description names the snapshot, which can not be shared with others
+            if manifest {
+                hasher.update(b"manifest");
+                hasher.update(snapshot.id().as_bytes());
+                hasher.update(source.as_ref().as_os_str().as_encoded_bytes());
+            }
+        } else {
+            hasher.update(b"file");
+
+            for blob in node.content.iter().flatten() {
+                hasher.update(blob.to_hex().as_bytes());
+            }
+        }
+
+        Ok(Self(hasher.finalize()))
+    }
+}
+
+impl fmt::Display for ContentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.to_hex().as_str())
+    }
+}
+
+impl From<ContentId> for String {
+    fn from(id: ContentId) -> Self {
+        id.0.to_hex().to_string()
+    }
+}
+
+impl TryFrom<String> for ContentId {
+    type Error = HexError;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        Ok(Self(Hash::from_hex(hex)?))
+    }
+}
+
 impl Deref for RestoreId {
     type Target = Hash;
 
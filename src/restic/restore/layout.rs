@@ -0,0 +1,93 @@
+use crate::restic::repository::Snapshot;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Arrangement of the entries within a restore archive, irrelevant for restores of a single file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveLayout {
+    /// Entries start at the restored folder
+    #[default]
+    Relative,
+    /// Entries keep their absolute path within the snapshot, to be put back where they came from
+    Full,
+    /// Entries start at the restored folder, wrapped in a folder named after the time of the snapshot
+    Dated,
+}
+
+impl ArchiveLayout {
+    pub const ALL: [Self; 3] = [Self::Relative, Self::Full, Self::Dated];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveLayout::Relative => "relative",
+            ArchiveLayout::Full => "full",
+            ArchiveLayout::Dated => "dated",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ArchiveLayout::Relative => "Contents of the folder",
+            ArchiveLayout::Full => "Full paths",
+            ArchiveLayout::Dated => "Folder named after the snapshot date",
+        }
+    }
+
+    /// Path within the snapshot at which entry paths are cut off.
+    pub(super) fn base(&self, source: &Path) -> PathBuf {
+        match self {
+            ArchiveLayout::Full => PathBuf::new(),
+            ArchiveLayout::Relative | ArchiveLayout::Dated => source.to_owned(),
+        }
+    }
+
+    /// Folder within the archive in which all entries are placed.
+    pub(super) fn folder(&self, snapshot: &Snapshot) -> PathBuf {
+        match self {
+            ArchiveLayout::Relative | ArchiveLayout::Full => PathBuf::new(),
+            ArchiveLayout::Dated => snapshot
+                .info()
+                .time
+                .format("%Y-%m-%d_%H-%M-%S")
+                .to_string()
+                .into(),
+        }
+    }
+}
+
+impl fmt::Display for ArchiveLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ArchiveLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|layout| layout.as_str() == s)
+            .ok_or_else(|| format!("unknown archive layout `{s}`"))
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn use_the_same_names_in_arguments_and_urls() {
+        for layout in ArchiveLayout::ALL {
+            let serialized = serde_json::to_string(&layout).unwrap();
+
+            assert_eq!(serialized, format!(r#""{layout}""#));
+            assert_eq!(layout.as_str().parse::<ArchiveLayout>(), Ok(layout));
+        }
+    }
+}
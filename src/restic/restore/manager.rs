@@ -1,74 +1,252 @@
-use super::{progress::ProgressReceiver, RestoreContent, RestoreId};
+use super::{
+    progress::ProgressReceiver, storage::Storage, ArchiveLayout, ContentId, RestoreContent,
+    RestoreFailure, RestoreFilter, RestoreId, ShareToken,
+};
+use crate::crypto::{Key, StreamCipher, NONCE_LEN};
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use index::DataIndex;
+use lock::{ClaimSet, LockMap};
+use queue::RestoreQueue;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     collections::HashMap,
-    fs, io,
-    path::{Path, PathBuf},
-    sync::{Arc, Mutex, RwLock},
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
 };
 
+mod failure;
 mod fetch;
+mod index;
+mod lock;
+mod overview;
 mod purge;
+mod queue;
+mod quota;
+mod recovery;
 mod restore;
+mod scrub;
+mod share;
+
+pub use overview::RestoreSummary;
+pub use purge::RetentionPolicy;
+pub use queue::ConcurrencyLimits;
+pub use quota::StorageLimits;
+pub use share::TICKET_LIFETIME;
 
 const META_DIR: &str = "meta";
 const DATA_DIR: &str = "data";
+const SHARE_DIR: &str = "shares";
+
+/// Version of the metadata schema, to be increased whenever records need to be migrated.
+/// Hashes of restores before version `2` were calculated while writing and do not match archives.
+const METADATA_VERSION: u32 = 2;
+
+const CONTEXT_DATA_KEY: &str = "restic-dl 2024-06 restore data key";
+/// Length of the random salt from which the key and nonce of a restore's data are derived
+const KEY_SALT_LEN: usize = 32;
 
 #[derive(Serialize, Deserialize)]
 struct RestoreMetadata {
+    /// Schema version the record has been written in, `0` for records which predate versioning
+    #[serde(default)]
+    version: u32,
     id: RestoreId,
     source: PathBuf,
     content: RestoreContent,
+    /// Absent for restores which predate choosing a layout
+    #[serde(default)]
+    layout: Option<ArchiveLayout>,
+    #[serde(default)]
+    filter: RestoreFilter,
+    /// Snapshot relative to which only changes are restored
+    #[serde(default)]
+    base_snapshot: Option<String>,
+    /// Whether the archive contains a checksum manifest
+    #[serde(default)]
+    manifest: bool,
     hash: Option<blake3::Hash>,
     created_at: DateTime<Utc>,
+    #[serde(default)]
+    repository: Option<String>,
+    #[serde(default)]
+    snapshot: Option<String>,
+    /// Overrides the expiry derived from the configured restore lifetime
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    downloads: u64,
+    #[serde(default)]
+    last_access: Option<DateTime<Utc>>,
+    /// Exempts the restore from being purged
+    #[serde(default)]
+    pinned: bool,
+    /// Hex-encoded salt from which the data encryption key is derived, absent for plain text restores
+    #[serde(default)]
+    key_salt: Option<String>,
+    /// Identity of the restored data, which is shared with all restores of the same content.
+    /// Absent for restores which predate deduplication and own their data exclusively.
+    #[serde(default)]
+    content_id: Option<ContentId>,
+    /// Reason why the restore could not be completed
+    #[serde(default)]
+    failure: Option<RestoreFailure>,
+    /// Entries which could not be read and are listed in the error report instead
+    #[serde(default)]
+    errors: u64,
+    /// Handle of the session which requested the restore, absent for restores which predate recording it
+    #[serde(default)]
+    owner: Option<String>,
 }
 
 #[derive(Clone)]
 pub struct RestoreManager {
-    root: PathBuf,
+    storage: Arc<dyn Storage>,
     progress: Arc<Mutex<HashMap<RestoreId, ProgressReceiver>>>,
-    purge_lock: Arc<RwLock<()>>,
-    restore_lifetime_days: u32,
-    keep_full_paths: bool,
+    metadata_lock: Arc<Mutex<()>>,
+    /// Space reserved by restores which are still being written
+    reservations: Arc<Mutex<HashMap<RestoreId, u64>>>,
+    /// Held while the data for a content is written, so that identical restores wait for it instead
+    artifact_locks: Arc<LockMap<ContentId>>,
+    /// Held while a restore is set up or removed, so that concurrent requests for it end up with a single job
+    restore_locks: Arc<LockMap<RestoreId>>,
+    /// Data keys which running restores write or link to, hence must not be removed
+    data_claims: Arc<ClaimSet<String>>,
+    data_index: Arc<DataIndex>,
+    queue: Arc<RestoreQueue>,
+    retention: RetentionPolicy,
+    /// Layout of restores for which none has been chosen
+    default_layout: ArchiveLayout,
+    limits: StorageLimits,
+    secret: Arc<Key>,
 }
 
 impl RestoreManager {
     pub fn new(
-        root: impl AsRef<Path>,
-        restore_lifetime_days: u32,
-        keep_full_paths: bool,
+        storage: Arc<dyn Storage>,
+        retention: RetentionPolicy,
+        default_layout: ArchiveLayout,
+        limits: StorageLimits,
+        concurrency: ConcurrencyLimits,
+        secret: Key,
     ) -> io::Result<Self> {
         assert!(
-            restore_lifetime_days > 0,
+            retention.idle_days > 0,
             "restore lifetime must be larger than `0`"
         );
+        assert!(
+            concurrency.workers > 0,
+            "number of restore workers must be larger than `0`"
+        );
 
-        let root: PathBuf = root.as_ref().into();
-        fs::create_dir_all(&root)?;
-        fs::create_dir_all(root.join(META_DIR))?;
-        fs::create_dir_all(root.join(DATA_DIR))?;
-
-        let manager = Self {
-            root,
-            progress: Arc::new(Mutex::new(HashMap::new())),
-            purge_lock: Arc::new(RwLock::new(())),
-            restore_lifetime_days,
-            keep_full_paths,
-        };
+        let manager = Self::unstarted(
+            storage,
+            retention,
+            default_layout,
+            limits,
+            concurrency.per_user,
+            secret,
+        );
 
+        manager.recover()?;
+        manager.fail_interrupted()?;
         manager.purge()?;
         manager.schedule_purge();
+        manager.schedule_scrub();
+        manager.spawn_workers(concurrency.workers);
 
         Ok(manager)
     }
 
-    fn meta_path(&self, id: RestoreId) -> PathBuf {
-        self.root.join(META_DIR).join(format!("{id}.json"))
+    /// Sets up the manager without looking at existing records or starting any threads.
+    fn unstarted(
+        storage: Arc<dyn Storage>,
+        retention: RetentionPolicy,
+        default_layout: ArchiveLayout,
+        limits: StorageLimits,
+        per_user: Option<usize>,
+        secret: Key,
+    ) -> Self {
+        Self {
+            storage,
+            progress: Arc::new(Mutex::new(HashMap::new())),
+            metadata_lock: Arc::new(Mutex::new(())),
+            reservations: Arc::new(Mutex::new(HashMap::new())),
+            artifact_locks: Arc::new(LockMap::new()),
+            restore_locks: Arc::new(LockMap::new()),
+            data_claims: Arc::new(ClaimSet::new()),
+            data_index: Arc::new(DataIndex::new()),
+            queue: Arc::new(RestoreQueue::new(per_user)),
+            retention,
+            default_layout,
+            limits,
+            secret: Arc::new(secret),
+        }
+    }
+
+    fn meta_key(id: RestoreId) -> String {
+        format!("{META_DIR}/{id}.json")
+    }
+
+    fn data_key(metadata: &RestoreMetadata) -> String {
+        match metadata.content_id {
+            Some(content_id) => Self::content_data_key(content_id),
+            None => format!("{DATA_DIR}/{}.bin", metadata.id),
+        }
+    }
+
+    fn content_data_key(content_id: ContentId) -> String {
+        format!("{DATA_DIR}/{content_id}.bin")
+    }
+
+    fn share_key(token: ShareToken) -> String {
+        format!("{SHARE_DIR}/{token}.json")
+    }
+
+    /// Keys of all objects in the directory which carry the given extension.
+    fn enumerate(&self, directory: &str, extension: &str) -> io::Result<Vec<String>> {
+        let mut keys = self.storage.list(directory)?;
+        keys.retain(|key| key.ends_with(&format!(".{extension}")));
+        Ok(keys)
+    }
+
+    fn read_json<T: DeserializeOwned>(&self, key: &str) -> io::Result<T> {
+        Ok(serde_json::from_slice(&self.storage.read(key)?)?)
+    }
+
+    fn write_json<T: Serialize>(&self, key: &str, value: &T) -> io::Result<()> {
+        self.storage.write(key, &serde_json::to_vec(value)?)
+    }
+
+    /// Creates the cipher for the data of a restore. Deleting the salt renders the data unrecoverable.
+    /// The salt is unique to the data, hence doubles as nonce of the stream.
+    fn data_cipher(&self, salt: &[u8; KEY_SALT_LEN]) -> StreamCipher {
+        let key = Key::derive(CONTEXT_DATA_KEY, &[self.secret.as_bytes(), salt]);
+        let nonce = salt[..NONCE_LEN]
+            .try_into()
+            .expect("salt is longer than a nonce");
+
+        StreamCipher::new(&key, nonce)
+    }
+
+    /// Decodes the salt stored with a restore into the cipher for its data, if it is encrypted.
+    fn metadata_cipher(&self, metadata: &RestoreMetadata) -> io::Result<Option<StreamCipher>> {
+        let Some(encoded) = &metadata.key_salt else {
+            return Ok(None);
+        };
+
+        let mut salt = [0; KEY_SALT_LEN];
+        hex::decode_to_slice(encoded, &mut salt)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok(Some(self.data_cipher(&salt)))
     }
 
-    fn data_path(&self, id: RestoreId) -> PathBuf {
-        self.root.join(DATA_DIR).join(format!("{id}.bin"))
+    /// Stops a running or queued restore, whose partial data is discarded.
+    pub fn cancel(&self, id: RestoreId) -> io::Result<()> {
+        self.progress(id)?.cancel();
+        self.dequeue(id);
+        Ok(())
     }
 
     pub fn progress(&self, id: RestoreId) -> io::Result<ProgressReceiver> {
@@ -83,3 +261,51 @@ impl RestoreManager {
             ))
     }
 }
+
+#[cfg(test)]
+impl RestoreManager {
+    /// Manager storing restores in a local directory, keeping idle restores for a day.
+    pub fn for_tests(root: &std::path::Path) -> Self {
+        Self::unstarted(
+            Arc::new(super::storage::LocalStorage::new(root).unwrap()),
+            RetentionPolicy {
+                idle_days: 1,
+                max_age_days: None,
+            },
+            ArchiveLayout::default(),
+            StorageLimits::default(),
+            None,
+            Key::generate(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl RestoreMetadata {
+    /// Record of a single file restore which is still being written.
+    fn for_tests(id: RestoreId) -> Self {
+        Self {
+            version: METADATA_VERSION,
+            id,
+            source: PathBuf::from("/home/user/report.pdf"),
+            content: RestoreContent::File { size: 0 },
+            layout: None,
+            filter: RestoreFilter::default(),
+            base_snapshot: None,
+            manifest: false,
+            hash: None,
+            created_at: Utc::now(),
+            repository: None,
+            snapshot: None,
+            expires_at: None,
+            downloads: 0,
+            last_access: None,
+            pinned: false,
+            key_salt: None,
+            content_id: None,
+            failure: None,
+            errors: 0,
+            owner: None,
+        }
+    }
+}
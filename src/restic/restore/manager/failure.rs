@@ -0,0 +1,84 @@
+use super::{RestoreManager, RestoreMetadata, META_DIR};
+use crate::restic::restore::{FailureKind, RestoreFailure, RestoreId};
+use std::io;
+
+impl RestoreManager {
+    /// Records why the restore could not be completed and discards the data written so far.
+    pub(super) fn fail(&self, id: RestoreId, failure: RestoreFailure) -> io::Result<()> {
+        eprintln!("Marking restore `{id}` as failed ({failure})");
+
+        self.update_metadata(id, |metadata| {
+            metadata.hash = None;
+            metadata.failure = Some(failure);
+        })?;
+
+        self.discard_partial_data(&self.fetch_metadata(id)?)
+    }
+
+    /// Removes incomplete data unless another restore of the same content is using or writing it.
+    fn discard_partial_data(&self, metadata: &RestoreMetadata) -> io::Result<()> {
+        let data_key = Self::data_key(metadata);
+
+        // Restores claim the data before recording any reference to it
+        self.data_claims.with_unclaimed(&data_key, || {
+            let is_used = self
+                .data_index
+                .referencing(&data_key)
+                .iter()
+                .any(|(id, other)| *id != metadata.id && !other.failed);
+
+            if !is_used {
+                self.storage.delete(&data_key).ok();
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Fails restores which were still running when the server was stopped.
+    /// They can be retried by the user once the repository is unlocked again.
+    pub(super) fn fail_interrupted(&self) -> io::Result<()> {
+        for key in self.enumerate(META_DIR, "json")? {
+            let Ok(metadata) = self.read_json::<RestoreMetadata>(&key) else {
+                continue;
+            };
+
+            if metadata.hash.is_none() && metadata.failure.is_none() {
+                let failure = RestoreFailure::new(
+                    FailureKind::Interrupted,
+                    "Interrupted by a server restart",
+                );
+                self.fail(metadata.id, failure)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use crate::restic::restore::progress::ProgressTracker;
+
+    #[test]
+    fn fail_interrupted_restores_for_retrying() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = RestoreManager::for_tests(root.path());
+        let metadata = RestoreMetadata::for_tests(RestoreId::try_from("01".repeat(32)).unwrap());
+        let data_key = RestoreManager::data_key(&metadata);
+
+        manager
+            .write_json(&RestoreManager::meta_key(metadata.id), &metadata)
+            .unwrap();
+        manager.index_metadata(&metadata);
+        manager.storage.write(&data_key, b"partial").unwrap();
+
+        manager.fail_interrupted().unwrap();
+
+        let failure = manager.fetch_metadata(metadata.id).unwrap().failure;
+        assert_eq!(failure.map(|f| f.kind), Some(FailureKind::Interrupted));
+        assert!(manager.storage.size(&data_key).is_err());
+        assert!(manager.track(metadata.id, &ProgressTracker::new()));
+    }
+}
@@ -1,24 +1,42 @@
 use super::{RestoreManager, RestoreMetadata};
 use crate::restic::restore::{Restore, RestoreId, RestoreState};
-use std::{fs::File, io};
+use std::{io, time::Duration};
 use tokio::task::spawn_blocking;
 
+/// Validity of presigned download URLs, enough to get a download started
+const PRESIGN_LIFETIME: Duration = Duration::from_secs(15 * 60);
+
 impl RestoreManager {
-    fn fetch_metadata(&self, id: RestoreId) -> io::Result<RestoreMetadata> {
-        Ok(serde_json::from_reader(File::open(self.meta_path(id))?)?)
+    pub(super) fn fetch_metadata(&self, id: RestoreId) -> io::Result<RestoreMetadata> {
+        self.read_json(&Self::meta_key(id))
     }
 
     fn fetch_sync(&self, id: RestoreId) -> io::Result<Restore> {
         let metadata = self.fetch_metadata(id)?;
         let data = metadata
             .hash
-            .map(|hash| (hash, File::open(self.data_path(id))));
+            .map(|hash| (hash, self.storage.open(&Self::data_key(&metadata))));
+
+        let cipher = self.metadata_cipher(&metadata)?;
+
+        let state = if let Some(failure) = metadata.failure.clone() {
+            RestoreState::Failed(failure)
+        } else if let Some((hash, data)) = data {
+            // Encrypted data has to pass through us to be decrypted
+            let presigned = cipher
+                .is_none()
+                .then(|| {
+                    let file_name = Restore::file_name_for(&metadata.source, &metadata.content);
+                    self.storage
+                        .presign(&Self::data_key(&metadata), &file_name, PRESIGN_LIFETIME)
+                })
+                .flatten();
 
-        let state = if let Some((hash, file)) = data {
             RestoreState::Available {
-                file: file?,
+                data: data?,
                 hash,
-                content: metadata.content,
+                cipher,
+                presigned,
             }
         } else if let Ok(progress) = self.progress(id) {
             RestoreState::InProgress(progress)
@@ -32,7 +50,16 @@ impl RestoreManager {
         Ok(Restore {
             id,
             state,
+            expires_at: self.expiry(&metadata),
             source: metadata.source,
+            content: metadata.content,
+            layout: metadata.layout,
+            filter: metadata.filter,
+            base_snapshot: metadata.base_snapshot,
+            manifest: metadata.manifest,
+            repository: metadata.repository,
+            snapshot: metadata.snapshot,
+            owner: metadata.owner,
         })
     }
 
@@ -0,0 +1,88 @@
+use super::{RestoreManager, RestoreMetadata};
+use crate::restic::restore::RestoreId;
+use std::{collections::HashMap, sync::Mutex};
+
+/// What a restore's record says about the data it references.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct DataReference {
+    pub data_key: String,
+    /// Whether the data has been written completely
+    pub complete: bool,
+    pub failed: bool,
+}
+
+/// Data referenced by each recorded restore, kept in memory so that finding the restores of some data
+/// does not require reading all records. Updated whenever a record is written or removed.
+pub(super) struct DataIndex(Mutex<HashMap<RestoreId, DataReference>>);
+
+impl DataIndex {
+    pub fn new() -> Self {
+        Self(Mutex::new(HashMap::new()))
+    }
+
+    pub fn insert(&self, id: RestoreId, reference: DataReference) {
+        self.0
+            .lock()
+            .expect("data index poisoned")
+            .insert(id, reference);
+    }
+
+    pub fn remove(&self, id: RestoreId) {
+        self.0.lock().expect("data index poisoned").remove(&id);
+    }
+
+    /// Restores which reference the data, along with the state of their data.
+    pub fn referencing(&self, data_key: &str) -> Vec<(RestoreId, DataReference)> {
+        self.0
+            .lock()
+            .expect("data index poisoned")
+            .iter()
+            .filter(|(_, reference)| reference.data_key == data_key)
+            .map(|(id, reference)| (*id, reference.clone()))
+            .collect()
+    }
+}
+
+impl RestoreManager {
+    pub(super) fn index_metadata(&self, metadata: &RestoreMetadata) {
+        let reference = DataReference {
+            data_key: Self::data_key(metadata),
+            complete: metadata.hash.is_some(),
+            failed: metadata.failure.is_some(),
+        };
+
+        self.data_index.insert(metadata.id, reference);
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn reference(data_key: &str, complete: bool) -> DataReference {
+        DataReference {
+            data_key: data_key.to_string(),
+            complete,
+            failed: false,
+        }
+    }
+
+    #[test]
+    fn find_restores_by_their_data() {
+        let index = DataIndex::new();
+        let first = RestoreId::try_from("01".repeat(32)).unwrap();
+        let second = RestoreId::try_from("02".repeat(32)).unwrap();
+
+        index.insert(first, reference("data/a.bin", false));
+        index.insert(second, reference("data/b.bin", true));
+        index.insert(first, reference("data/a.bin", true));
+
+        assert_eq!(
+            index.referencing("data/a.bin"),
+            [(first, reference("data/a.bin", true))]
+        );
+
+        index.remove(second);
+        assert!(index.referencing("data/b.bin").is_empty());
+    }
+}
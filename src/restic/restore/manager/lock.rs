@@ -0,0 +1,144 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::{Arc, Condvar, Mutex},
+};
+
+/// Hands out one mutex per key, forgetting about those which are no longer in use.
+pub(super) struct LockMap<K>(Mutex<HashMap<K, Arc<Mutex<()>>>>);
+
+impl<K: Eq + Hash> LockMap<K> {
+    pub fn new() -> Self {
+        Self(Mutex::new(HashMap::new()))
+    }
+
+    /// Returns the mutex for the key, which has to be locked by the caller.
+    pub fn get(&self, key: K) -> Arc<Mutex<()>> {
+        let mut locks = self.0.lock().expect("lock map poisoned");
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        locks.entry(key).or_default().clone()
+    }
+}
+
+/// Keys which are in use and have to be left alone, e.g. by purging.
+/// The same key may be claimed several times and stays in use until every claim has been dropped.
+pub(super) struct ClaimSet<K> {
+    state: Mutex<ClaimState<K>>,
+    released: Condvar,
+}
+
+struct ClaimState<K> {
+    claims: HashMap<K, usize>,
+    /// Keys which are being worked on while unclaimed, claiming them has to wait
+    exclusive: HashSet<K>,
+}
+
+pub(super) struct Claim<'s, K: Eq + Hash> {
+    set: &'s ClaimSet<K>,
+    key: K,
+}
+
+/// Makes the key available again once the work on the unclaimed key is done, even if it panicked.
+struct Exclusive<'s, K: Eq + Hash> {
+    set: &'s ClaimSet<K>,
+    key: &'s K,
+}
+
+impl<K: Eq + Hash + Clone> ClaimSet<K> {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(ClaimState {
+                claims: HashMap::new(),
+                exclusive: HashSet::new(),
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Claims the key, waiting for work on it to finish if it is unclaimed right now.
+    pub fn claim(&self, key: K) -> Claim<'_, K> {
+        let mut state = self.state.lock().expect("claim set poisoned");
+
+        while state.exclusive.contains(&key) {
+            state = self.released.wait(state).expect("claim set poisoned");
+        }
+
+        *state.claims.entry(key.clone()).or_default() += 1;
+
+        Claim { set: self, key }
+    }
+
+    /// Runs the function unless the key is claimed, preventing any claims of it from being made meanwhile.
+    /// Also skips the function while another one runs for the same key. Other keys are not held up.
+    pub fn with_unclaimed<R>(&self, key: &K, f: impl FnOnce() -> R) -> Option<R> {
+        let mut state = self.state.lock().expect("claim set poisoned");
+
+        if state.claims.contains_key(key) || !state.exclusive.insert(key.clone()) {
+            return None;
+        }
+
+        drop(state);
+
+        let _exclusive = Exclusive { set: self, key };
+
+        Some(f())
+    }
+}
+
+impl<K: Eq + Hash> Drop for Claim<'_, K> {
+    fn drop(&mut self) {
+        let mut state = self.set.state.lock().expect("claim set poisoned");
+
+        if let Some(count) = state.claims.get_mut(&self.key) {
+            *count -= 1;
+
+            if *count == 0 {
+                state.claims.remove(&self.key);
+            }
+        }
+    }
+}
+
+impl<K: Eq + Hash> Drop for Exclusive<'_, K> {
+    fn drop(&mut self) {
+        let mut state = self.set.state.lock().expect("claim set poisoned");
+        state.exclusive.remove(self.key);
+        self.set.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn keep_keys_claimed_until_the_last_claim_is_dropped() {
+        let claims = ClaimSet::new();
+
+        let first = claims.claim("data/a.bin");
+        let second = claims.claim("data/a.bin");
+        drop(first);
+        assert_eq!(claims.with_unclaimed(&"data/a.bin", || ()), None);
+
+        drop(second);
+        assert_eq!(claims.with_unclaimed(&"data/a.bin", || ()), Some(()));
+    }
+
+    #[test]
+    fn hold_up_only_the_unclaimed_key() {
+        let claims = ClaimSet::new();
+
+        let nested = claims.with_unclaimed(&"data/a.bin", || {
+            // The set itself is not locked while working on the key
+            drop(claims.claim("data/b.bin"));
+
+            (
+                claims.with_unclaimed(&"data/a.bin", || ()),
+                claims.with_unclaimed(&"data/b.bin", || ()),
+            )
+        });
+
+        assert_eq!(nested, Some((None, Some(()))));
+        drop(claims.claim("data/a.bin"));
+    }
+}
@@ -0,0 +1,156 @@
+use super::{RestoreManager, RestoreMetadata, META_DIR};
+use crate::restic::restore::{RestoreContent, RestoreFailure, RestoreId};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::{cmp::Reverse, io, path::PathBuf};
+use tokio::task::spawn_blocking;
+
+/// Overview of a stored restore as shown on the management dashboard.
+pub struct RestoreSummary {
+    pub id: RestoreId,
+    pub source: PathBuf,
+    pub repository: Option<String>,
+    pub snapshot: Option<String>,
+    pub content: RestoreContent,
+    /// Size of the stored data, unavailable while the restore is still running or has failed
+    pub size: Option<u64>,
+    pub failure: Option<RestoreFailure>,
+    /// Entries skipped because they could not be read
+    pub errors: u64,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub pinned: bool,
+    pub downloads: u64,
+    pub last_access: Option<DateTime<Utc>>,
+    pub shares: usize,
+    pub active_shares: usize,
+}
+
+impl RestoreManager {
+    fn list_sync(&self) -> io::Result<Vec<RestoreSummary>> {
+        let shares = self.enumerate_shares()?;
+        let mut restores = Vec::new();
+
+        for key in self.enumerate(META_DIR, "json")? {
+            // Restores might vanish at any time due to purging or deletion
+            let Ok(metadata) = self.read_json::<RestoreMetadata>(&key) else {
+                continue;
+            };
+
+            let id = metadata.id;
+            let size = metadata
+                .hash
+                .and_then(|_| self.storage.size(&Self::data_key(&metadata)).ok());
+
+            let shares = shares.iter().filter(|share| share.restore == id);
+
+            restores.push(RestoreSummary {
+                id,
+                expires_at: self.expiry(&metadata),
+                pinned: metadata.pinned,
+                failure: metadata.failure,
+                errors: metadata.errors,
+                source: metadata.source,
+                repository: metadata.repository,
+                snapshot: metadata.snapshot,
+                content: metadata.content,
+                size,
+                created_at: metadata.created_at,
+                downloads: metadata.downloads,
+                last_access: metadata.last_access,
+                shares: shares.clone().count(),
+                active_shares: shares.filter(|share| share.revoked_at.is_none()).count(),
+            });
+        }
+
+        restores.sort_by_key(|restore| Reverse(restore.created_at));
+
+        Ok(restores)
+    }
+
+    /// Lists all restores currently held in storage, most recent first.
+    pub async fn list(&self) -> io::Result<Vec<RestoreSummary>> {
+        let manager = self.clone();
+        spawn_blocking(move || manager.list_sync()).await?
+    }
+
+    pub(super) fn record_access_sync(&self, id: RestoreId) -> io::Result<()> {
+        self.update_metadata(id, |metadata| {
+            metadata.downloads += 1;
+            metadata.last_access = Some(Utc::now());
+        })
+    }
+
+    /// Counts a download of the restore, regardless of whether it happened through a share.
+    pub async fn record_access(&self, id: RestoreId) -> io::Result<()> {
+        let manager = self.clone();
+        spawn_blocking(move || manager.record_access_sync(id)).await?
+    }
+
+    /// Pushes the expiry of the restore back by the configured idle period.
+    pub async fn extend(&self, id: RestoreId) -> io::Result<()> {
+        let manager = self.clone();
+
+        spawn_blocking(move || {
+            let lifetime = TimeDelta::days(manager.retention.idle_days as i64);
+
+            manager.update_metadata(id, |metadata| {
+                let now = Utc::now();
+                let expiry = manager.expiry(metadata).unwrap_or(now).max(now);
+                metadata.expires_at = Some(expiry + lifetime);
+            })
+        })
+        .await?
+    }
+
+    /// Exempts the restore from being purged, or makes it subject to the retention rules again.
+    pub async fn pin(&self, id: RestoreId, pinned: bool) -> io::Result<()> {
+        let manager = self.clone();
+        spawn_blocking(move || manager.update_metadata(id, |metadata| metadata.pinned = pinned))
+            .await?
+    }
+
+    /// Revokes all shares of the restore.
+    pub async fn revoke_shares(&self, id: RestoreId) -> io::Result<()> {
+        for share in self.shares(id).await? {
+            if share.revoked_at.is_none() {
+                self.revoke_share(share.token).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the restore right away instead of waiting for it to expire, revoking its shares.
+    pub async fn delete(&self, id: RestoreId) -> io::Result<()> {
+        let manager = self.clone();
+
+        spawn_blocking(move || {
+            // Prevents the restore from being started again while it is removed
+            let restore_lock = manager.restore_locks.get(id);
+            let _guard = restore_lock.lock().expect("restore lock poisoned");
+
+            if manager.progress(id).is_ok() {
+                return Err(io::Error::other("restore is still in progress"));
+            }
+
+            manager.remove(id)
+        })
+        .await?
+    }
+
+    /// Removes the records of a restore along with its data, unless the data is shared with other restores.
+    /// Its shares are revoked but kept for auditing. Callers have to make sure it is not in progress.
+    pub(super) fn remove(&self, id: RestoreId) -> io::Result<()> {
+        for share in self.enumerate_shares()? {
+            if share.restore == id {
+                self.revoke_share_sync(share.token)?;
+            }
+        }
+
+        let metadata = self.fetch_metadata(id)?;
+        self.delete_metadata(id)?;
+        self.release_data(&metadata)?;
+
+        Ok(())
+    }
+}
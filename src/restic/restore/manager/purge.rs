@@ -1,48 +1,77 @@
 use super::{RestoreManager, RestoreMetadata, DATA_DIR, META_DIR};
-use chrono::Utc;
+use crate::restic::restore::RestoreId;
+use chrono::{DateTime, TimeDelta, Utc};
 use std::{
-    fs::{self, File},
     io,
-    path::{Path, PathBuf},
     thread::{self, sleep},
     time::Duration,
 };
 
+/// Rules deciding how long restores are kept, in days.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    /// Time a restore is kept after it has last been downloaded, or created if it never was
+    pub idle_days: u32,
+    /// Time after which a restore is purged regardless of its use
+    pub max_age_days: Option<u32>,
+}
+
 impl RestoreManager {
-    fn enumerate_files(directory: &Path, extension: &str) -> io::Result<Vec<PathBuf>> {
-        let mut files = fs::read_dir(directory)?
-            .map(|res| res.map(|e| e.path()))
-            .collect::<Result<Vec<_>, io::Error>>()?;
-
-        files.retain(|entry| {
-            entry
-                .extension()
-                .map(|f| f.to_string_lossy() == extension)
-                .unwrap_or_default()
-        });
+    /// Point in time after which the restore is purged, none if it is pinned.
+    pub(super) fn expiry(&self, metadata: &RestoreMetadata) -> Option<DateTime<Utc>> {
+        if metadata.pinned {
+            return None;
+        }
+
+        // Restores are kept for the full last day of their lifetime
+        let days = |days: u32| TimeDelta::days(days as i64 + 1);
+
+        let last_used = metadata.last_access.unwrap_or(metadata.created_at);
+        let idle_expiry = last_used + days(self.retention.idle_days);
+        let expiry = match self.retention.max_age_days {
+            Some(max_age) => idle_expiry.min(metadata.created_at + days(max_age)),
+            None => idle_expiry,
+        };
+
+        // Manually extended restores are kept at least until the extension runs out
+        Some(
+            metadata
+                .expires_at
+                .map_or(expiry, |extended| extended.max(expiry)),
+        )
+    }
 
-        Ok(files)
+    fn is_expired(&self, metadata: &RestoreMetadata) -> bool {
+        self.expiry(metadata)
+            .is_some_and(|expiry| expiry <= Utc::now())
     }
 
     fn purge_expired(&self) -> io::Result<Vec<RestoreMetadata>> {
         let mut active_restores = Vec::new();
 
-        for path in Self::enumerate_files(&self.root.join(META_DIR), "json")? {
-            match serde_json::from_reader::<_, RestoreMetadata>(File::open(&path)?) {
-                Ok(metadata) => {
-                    let delta = Utc::now().signed_duration_since(metadata.created_at);
+        for key in self.enumerate(META_DIR, "json")? {
+            match self.read_json::<RestoreMetadata>(&key) {
+                Ok(metadata) if self.is_expired(&metadata) => {
+                    // Restores might get retried meanwhile, which records them anew
+                    let restore_lock = self.restore_locks.get(metadata.id);
+                    let _guard = restore_lock.lock().expect("restore lock poisoned");
 
-                    if delta.num_days() > self.restore_lifetime_days as i64 {
+                    let Ok(metadata) = self.fetch_metadata(metadata.id) else {
+                        continue;
+                    };
+
+                    if self.is_expired(&metadata) && self.progress(metadata.id).is_err() {
+                        // Data is removed with the orphans once no other restore references it
                         eprintln!("Purging expired restore `{}`", metadata.id);
-                        fs::remove_file(self.data_path(metadata.id)).ok();
-                        fs::remove_file(self.meta_path(metadata.id)).ok();
+                        self.delete_metadata(metadata.id).ok();
                     } else {
                         active_restores.push(metadata);
                     }
                 }
+                Ok(metadata) => active_restores.push(metadata),
                 Err(err) => {
-                    eprintln!("Failed to read metadata at {path:?} ({err}), it will be removed");
-                    fs::remove_file(path).ok();
+                    eprintln!("Failed to read metadata at {key:?} ({err}), it will be removed");
+                    self.storage.delete(&key).ok();
                 }
             }
         }
@@ -51,16 +80,16 @@ impl RestoreManager {
     }
 
     fn purge_orphaned_data(&self, active_restores: &Vec<RestoreMetadata>) -> io::Result<()> {
-        for path in Self::enumerate_files(&self.root.join(DATA_DIR), "bin")? {
+        for key in self.enumerate(DATA_DIR, "bin")? {
             let no_matching_metadata = active_restores
                 .iter()
-                .map(|restore| self.data_path(restore.id))
-                .find(|p| path == *p)
+                .map(Self::data_key)
+                .find(|k| key == *k)
                 .is_none();
 
-            if no_matching_metadata {
-                eprintln!("Removing orphaned data file at {path:?}");
-                fs::remove_file(path).ok();
+            // Restores might have been created since the list was taken, hence it is checked again
+            if no_matching_metadata && self.delete_unreferenced_data(&key, None)? {
+                eprintln!("Removed orphaned data file at {key:?}");
             }
         }
 
@@ -68,33 +97,80 @@ impl RestoreManager {
     }
 
     fn purge_orphaned_meta(&self, active_restores: Vec<RestoreMetadata>) -> io::Result<()> {
+        // Running restores are about to replace their data, e.g. when retried
         let completed_restores = active_restores
             .into_iter()
-            .filter(|restore| restore.hash.is_some());
+            .filter(|restore| restore.hash.is_some() && self.progress(restore.id).is_err());
 
         for restore in completed_restores {
-            if let Err(err) = fs::metadata(self.data_path(restore.id)) {
+            if let Err(err) = self.storage.size(&Self::data_key(&restore)) {
                 eprintln!(
                     "Missing or inaccessible data for restore `{}` ({err}), removing metadata",
                     restore.id
                 );
 
-                fs::remove_file(self.meta_path(restore.id)).ok();
+                self.delete_metadata(restore.id).ok();
             }
         }
 
         Ok(())
     }
 
-    pub(super) fn purge(&self) -> io::Result<()> {
-        let lock = self.purge_lock.write().expect("purge lock poisoned");
+    /// Revokes the shares of restores which are gone, keeping their records for auditing.
+    fn revoke_orphaned_shares(&self, active_restores: &[RestoreMetadata]) -> io::Result<()> {
+        for share in self.enumerate_shares()? {
+            let no_matching_restore = !active_restores
+                .iter()
+                .any(|restore| restore.id == share.restore);
+
+            // Shares might be created for restores which have been started since the list was taken
+            if share.revoked_at.is_none()
+                && no_matching_restore
+                && !self.storage.exists(&Self::meta_key(share.restore))?
+            {
+                eprintln!("Revoking share `{}` of vanished restore", share.token);
+                self.revoke_share_sync(share.token)?;
+            }
+        }
 
+        Ok(())
+    }
+
+    /// Removes the data of a restore unless other restores of the same content still reference it.
+    pub(super) fn release_data(&self, metadata: &RestoreMetadata) -> io::Result<()> {
+        self.delete_unreferenced_data(&Self::data_key(metadata), Some(metadata.id))
+            .map(drop)
+    }
+
+    /// Deletes the data unless a restore other than its owner references it or a running restore claims it.
+    /// Returns whether the data has been deleted.
+    fn delete_unreferenced_data(
+        &self,
+        data_key: &str,
+        owner: Option<RestoreId>,
+    ) -> io::Result<bool> {
+        // Restores claim the data before recording any reference to it
+        let deleted = self.data_claims.with_unclaimed(&data_key.to_string(), || {
+            let is_used = self
+                .data_index
+                .referencing(data_key)
+                .iter()
+                .any(|(id, _)| Some(*id) != owner);
+
+            !is_used && self.storage.delete(data_key).is_ok()
+        });
+
+        Ok(deleted.unwrap_or(false))
+    }
+
+    /// Removes expired restores and anything left behind by them.
+    /// Running restores protect their records and data, hence purging does not have to wait for them.
+    pub(super) fn purge(&self) -> io::Result<()> {
         let active_restores = self.purge_expired()?;
         self.purge_orphaned_data(&active_restores)?;
+        self.revoke_orphaned_shares(&active_restores)?;
         self.purge_orphaned_meta(active_restores)?;
 
-        drop(lock);
-
         Ok(())
     }
 
@@ -113,3 +189,64 @@ impl RestoreManager {
         });
     }
 }
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn id(byte: &str) -> RestoreId {
+        RestoreId::try_from(byte.repeat(32)).unwrap()
+    }
+
+    #[test]
+    fn expire_restores_once_idle() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = RestoreManager::for_tests(root.path());
+        let mut metadata = RestoreMetadata::for_tests(id("01"));
+        metadata.created_at = Utc::now() - TimeDelta::days(3);
+
+        assert!(manager.is_expired(&metadata));
+
+        metadata.last_access = Some(Utc::now() - TimeDelta::hours(1));
+        assert!(!manager.is_expired(&metadata));
+    }
+
+    #[test]
+    fn expire_old_restores_despite_use() {
+        let root = tempfile::tempdir().unwrap();
+        let mut manager = RestoreManager::for_tests(root.path());
+        manager.retention.max_age_days = Some(5);
+
+        let mut metadata = RestoreMetadata::for_tests(id("01"));
+        metadata.created_at = Utc::now() - TimeDelta::days(10);
+        metadata.last_access = Some(Utc::now());
+
+        assert!(manager.is_expired(&metadata));
+    }
+
+    #[test]
+    fn keep_pinned_restores() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = RestoreManager::for_tests(root.path());
+
+        let mut expired = RestoreMetadata::for_tests(id("01"));
+        expired.created_at = Utc::now() - TimeDelta::days(30);
+
+        let mut pinned = RestoreMetadata::for_tests(id("02"));
+        pinned.created_at = expired.created_at;
+        pinned.pinned = true;
+
+        for metadata in [&expired, &pinned] {
+            manager
+                .write_json(&RestoreManager::meta_key(metadata.id), metadata)
+                .unwrap();
+        }
+
+        assert_eq!(manager.expiry(&pinned), None);
+
+        manager.purge().unwrap();
+
+        assert!(manager.fetch_metadata(expired.id).is_err());
+        assert!(manager.fetch_metadata(pinned.id).is_ok());
+    }
+}
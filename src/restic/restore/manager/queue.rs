@@ -0,0 +1,241 @@
+use crate::restic::{
+    repository::Snapshot,
+    restore::{
+        progress::{ProgressTracker, Status},
+        stream::DataSender,
+        ArchiveLayout, RestoreFilter, RestoreId,
+    },
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::{Condvar, Mutex},
+};
+
+/// Bounds on the number of restores which are written at the same time.
+#[derive(Debug, Clone, Copy)]
+pub struct ConcurrencyLimits {
+    pub workers: usize,
+    /// Restores of a single user which may be written at the same time, others wait for their turn
+    pub per_user: Option<usize>,
+}
+
+/// Restore which has been planned and waits for its data to be written.
+pub(super) struct Job {
+    pub id: RestoreId,
+    pub snapshot: Snapshot,
+    pub source: PathBuf,
+    pub layout: ArchiveLayout,
+    pub filter: RestoreFilter,
+    /// Snapshot relative to which only changes are restored
+    pub base: Option<Snapshot>,
+    pub manifest: bool,
+    pub owner: Option<String>,
+    pub progress: ProgressTracker,
+    /// Download which receives the data while it is written
+    pub stream: Option<DataSender>,
+}
+
+/// What the queue needs to know about the jobs waiting in it.
+pub(super) trait QueuedJob {
+    fn id(&self) -> RestoreId;
+    fn owner(&self) -> Option<&str>;
+    /// Tells the job where it is within the queue, starting at `1`.
+    fn set_position(&mut self, position: usize);
+}
+
+/// Restores waiting for a worker, in the order they were requested.
+pub(super) struct RestoreQueue<J = Job> {
+    state: Mutex<QueueState<J>>,
+    changed: Condvar,
+    per_user: Option<usize>,
+}
+
+struct QueueState<J> {
+    jobs: VecDeque<J>,
+    running: HashMap<String, usize>,
+}
+
+impl QueuedJob for Job {
+    fn id(&self) -> RestoreId {
+        self.id
+    }
+
+    fn owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+
+    fn set_position(&mut self, position: usize) {
+        self.progress.set_status(Status::Queued(position));
+    }
+}
+
+impl<J: QueuedJob> RestoreQueue<J> {
+    pub fn new(per_user: Option<usize>) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                jobs: VecDeque::new(),
+                running: HashMap::new(),
+            }),
+            changed: Condvar::new(),
+            per_user,
+        }
+    }
+
+    pub fn push(&self, job: J) {
+        let mut state = self.state.lock().expect("restore queue poisoned");
+        state.jobs.push_back(job);
+        state.announce_positions();
+
+        self.changed.notify_all();
+    }
+
+    /// Takes the job out of the queue unless a worker already picked it up.
+    pub fn remove(&self, id: RestoreId) -> Option<J> {
+        let mut state = self.state.lock().expect("restore queue poisoned");
+        let index = state.jobs.iter().position(|job| job.id() == id)?;
+        let job = state.jobs.remove(index);
+        state.announce_positions();
+
+        job
+    }
+
+    /// Blocks until a job is available whose owner has not reached their limit yet.
+    pub fn next(&self) -> J {
+        let mut state = self.state.lock().expect("restore queue poisoned");
+
+        loop {
+            let runnable = state.jobs.iter().position(|job| {
+                let running = job
+                    .owner()
+                    .and_then(|owner| state.running.get(owner))
+                    .copied()
+                    .unwrap_or_default();
+
+                self.per_user.is_none_or(|limit| running < limit)
+            });
+
+            if let Some(job) = runnable.and_then(|index| state.jobs.remove(index)) {
+                if let Some(owner) = job.owner() {
+                    *state.running.entry(owner.to_string()).or_default() += 1;
+                }
+
+                state.announce_positions();
+                return job;
+            }
+
+            state = self.changed.wait(state).expect("restore queue poisoned");
+        }
+    }
+
+    /// Marks a job taken through [`RestoreQueue::next`] as done.
+    pub fn done(&self, job: &J) {
+        let mut state = self.state.lock().expect("restore queue poisoned");
+
+        if let Some(owner) = job.owner() {
+            if let Some(running) = state.running.get_mut(owner) {
+                *running = running.saturating_sub(1);
+
+                if *running == 0 {
+                    state.running.remove(owner);
+                }
+            }
+        }
+
+        // Jobs held back by the per-user limit might be able to run now
+        self.changed.notify_all();
+    }
+}
+
+impl<J: QueuedJob> QueueState<J> {
+    fn announce_positions(&mut self) {
+        for (index, job) in self.jobs.iter_mut().enumerate() {
+            job.set_position(index + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct TestJob {
+        id: RestoreId,
+        owner: Option<String>,
+        position: Arc<AtomicUsize>,
+    }
+
+    fn id(n: u8) -> RestoreId {
+        RestoreId::try_from(format!("{n:02x}").repeat(32)).unwrap()
+    }
+
+    impl TestJob {
+        fn new(n: u8, owner: &str) -> Self {
+            Self {
+                id: id(n),
+                owner: Some(owner.to_string()),
+                position: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl QueuedJob for TestJob {
+        fn id(&self) -> RestoreId {
+            self.id
+        }
+
+        fn owner(&self) -> Option<&str> {
+            self.owner.as_deref()
+        }
+
+        fn set_position(&mut self, position: usize) {
+            self.position.store(position, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn hold_back_jobs_of_users_at_their_limit() {
+        let queue = RestoreQueue::new(Some(1));
+
+        queue.push(TestJob::new(1, "alice"));
+        queue.push(TestJob::new(2, "alice"));
+        queue.push(TestJob::new(3, "bob"));
+
+        let running = queue.next();
+        assert_eq!(running.id, id(1));
+
+        // Alice's second job has to wait for her first one, Bob's may go ahead
+        assert_eq!(queue.next().id, id(3));
+
+        queue.done(&running);
+        assert_eq!(queue.next().id, id(2));
+    }
+
+    #[test]
+    fn announce_positions_as_the_queue_moves() {
+        let queue = RestoreQueue::new(None);
+        let jobs = [
+            TestJob::new(1, "alice"),
+            TestJob::new(2, "bob"),
+            TestJob::new(3, "carol"),
+        ];
+        let positions = jobs.each_ref().map(|job| job.position.clone());
+        let current = || positions.each_ref().map(|p| p.load(Ordering::SeqCst));
+
+        for job in jobs {
+            queue.push(job);
+        }
+
+        assert_eq!(current(), [1, 2, 3]);
+
+        assert!(queue.remove(id(2)).is_some());
+        assert_eq!(current()[2], 2);
+
+        assert_eq!(queue.next().id, id(1));
+        assert_eq!(current()[2], 1);
+    }
+}
@@ -0,0 +1,140 @@
+use super::{RestoreManager, RestoreMetadata, DATA_DIR, META_DIR};
+use crate::{helper::format_size, restic::restore::RestoreId, Error, Result};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+
+/// Bounds on the disk space used by restores, in bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StorageLimits {
+    pub total: Option<u64>,
+    pub per_restore: Option<u64>,
+}
+
+impl RestoreManager {
+    /// Reserves space for a restore of the given size, evicting the least recently downloaded
+    /// restores if necessary. The reservation is held until [`RestoreManager::release`] is called.
+    pub(super) fn reserve(&self, id: RestoreId, size: u64) -> Result<()> {
+        if let Some(limit) = self.limits.per_restore.filter(|limit| size > *limit) {
+            return Err(Error::InsufficientStorage(format!(
+                "restore of {} exceeds the limit of {} per restore",
+                format_size(size),
+                format_size(limit)
+            )));
+        }
+
+        let mut reservations = self.reservations.lock().expect("reservations poisoned");
+
+        if let Some(total) = self.limits.total {
+            let mut used = self.usage(&reservations)?;
+
+            if used + size > total {
+                for (candidates, candidate_size) in self.eviction_candidates(&reservations)? {
+                    // Data is only freed once every restore sharing it is gone
+                    for candidate in candidates {
+                        eprintln!(
+                            "Evicting restore `{candidate}` to free {}",
+                            format_size(candidate_size)
+                        );
+
+                        self.remove(candidate)?;
+                    }
+
+                    used = used.saturating_sub(candidate_size);
+
+                    if used + size <= total {
+                        break;
+                    }
+                }
+            }
+
+            if used + size > total {
+                return Err(Error::InsufficientStorage(format!(
+                    "restore of {} does not fit into the remaining {} of restore storage",
+                    format_size(size),
+                    format_size(total.saturating_sub(used))
+                )));
+            }
+        }
+
+        reservations.insert(id, size);
+
+        Ok(())
+    }
+
+    pub(super) fn release(&self, id: RestoreId) {
+        self.reservations
+            .lock()
+            .expect("reservations poisoned")
+            .remove(&id);
+    }
+
+    /// Data keys written by running restores.
+    fn reserved_data(&self, reservations: &HashMap<RestoreId, u64>) -> HashSet<String> {
+        reservations
+            .keys()
+            .filter_map(|id| self.fetch_metadata(*id).ok())
+            .map(|metadata| Self::data_key(&metadata))
+            .collect()
+    }
+
+    /// Bytes used by completed restores plus the space reserved for running ones.
+    fn usage(&self, reservations: &HashMap<RestoreId, u64>) -> io::Result<u64> {
+        let mut used = reservations.values().sum();
+        let reserved = self.reserved_data(reservations);
+
+        for key in self.enumerate(DATA_DIR, "bin")? {
+            if !reserved.contains(&key) {
+                used += self.storage.size(&key).unwrap_or_default();
+            }
+        }
+
+        Ok(used)
+    }
+
+    /// Completed, unpinned restores grouped by the data they share along with its size,
+    /// least recently downloaded first.
+    fn eviction_candidates(
+        &self,
+        reservations: &HashMap<RestoreId, u64>,
+    ) -> io::Result<Vec<(Vec<RestoreId>, u64)>> {
+        let reserved = self.reserved_data(reservations);
+        let mut candidates = HashMap::<String, (DateTime<Utc>, Vec<RestoreId>, bool)>::new();
+
+        for key in self.enumerate(META_DIR, "json")? {
+            let Ok(metadata) = self.read_json::<RestoreMetadata>(&key) else {
+                continue;
+            };
+
+            let data_key = Self::data_key(&metadata);
+            let last_used = metadata.last_access.unwrap_or(metadata.created_at);
+            let (candidate_used, ids, retained) = candidates
+                .entry(data_key)
+                .or_insert_with(|| (last_used, Vec::new(), false));
+
+            *candidate_used = last_used.max(*candidate_used);
+            ids.push(metadata.id);
+
+            // Restores which are still running or pinned keep their data alive
+            *retained |= metadata.hash.is_none() || metadata.pinned;
+        }
+
+        let mut candidates = candidates
+            .into_iter()
+            .filter(|(data_key, (.., retained))| !retained && !reserved.contains(data_key))
+            .map(|(data_key, (last_used, ids, _))| {
+                let size = self.storage.size(&data_key).unwrap_or_default();
+                (last_used, ids, size)
+            })
+            .collect::<Vec<_>>();
+
+        candidates.sort_by_key(|(last_used, ..)| *last_used);
+
+        Ok(candidates
+            .into_iter()
+            .map(|(_, ids, size)| (ids, size))
+            .collect())
+    }
+}
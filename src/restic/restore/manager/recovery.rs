@@ -0,0 +1,98 @@
+use super::{RestoreManager, RestoreMetadata, METADATA_VERSION, META_DIR};
+use crate::restic::restore::{FailureKind, RestoreFailure};
+use std::io;
+
+impl RestoreManager {
+    /// Reconciles records and data after the server was stopped, which might have been a crash.
+    /// Runs before any restore is started, hence no locks are needed.
+    pub(super) fn recover(&self) -> io::Result<()> {
+        self.storage.recover()?;
+
+        let mut records = Vec::new();
+
+        for key in self.enumerate(META_DIR, "json")? {
+            match self.read_json::<RestoreMetadata>(&key) {
+                Ok(metadata) => records.push(metadata),
+                Err(err) => {
+                    // Without its key salt the data is lost anyway, the record is only kept for inspection
+                    eprintln!("Setting aside unreadable metadata at {key:?} ({err})");
+                    self.storage
+                        .write(&format!("{key}.corrupt"), &self.storage.read(&key)?)?;
+                    self.storage.delete(&key)?;
+                }
+            }
+        }
+
+        // Failing restores below decides whether their data is still referenced, which needs all of them indexed
+        for metadata in &records {
+            self.index_metadata(metadata);
+        }
+
+        for metadata in records {
+            if metadata.version > METADATA_VERSION {
+                eprintln!(
+                    "Restore `{}` has been recorded by a newer version of this server, some of its details may be lost",
+                    metadata.id
+                );
+            }
+
+            if metadata.hash.is_some() && !self.storage.exists(&Self::data_key(&metadata))? {
+                let failure =
+                    RestoreFailure::new(FailureKind::Other, "The restored data went missing");
+                self.fail(metadata.id, failure)?;
+            } else if metadata.version < 2 && metadata.hash.is_some() {
+                // Reading all of the data would delay the start, see `migrate_hashes`
+                continue;
+            } else if metadata.version < METADATA_VERSION {
+                // Updates write records in the current schema
+                self.update_metadata(metadata.id, |_| {})?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Hashes the data of restores recorded before version 2 as it is downloaded, rather than as it is stored.
+    /// Runs in the background as it reads all of their data.
+    pub(super) fn migrate_hashes(&self) -> io::Result<()> {
+        let is_outdated =
+            |metadata: &RestoreMetadata| metadata.version < 2 && metadata.hash.is_some();
+
+        for key in self.enumerate(META_DIR, "json")? {
+            let Ok(metadata) = self.read_json::<RestoreMetadata>(&key) else {
+                continue;
+            };
+
+            if !is_outdated(&metadata) {
+                continue;
+            }
+
+            // Restores might get retried or removed meanwhile
+            let restore_lock = self.restore_locks.get(metadata.id);
+            let _guard = restore_lock.lock().expect("restore lock poisoned");
+
+            let Ok(metadata) = self.fetch_metadata(metadata.id) else {
+                continue;
+            };
+
+            if !is_outdated(&metadata) {
+                continue;
+            }
+
+            eprintln!(
+                "Hashing data of restore `{}` as it is downloaded",
+                metadata.id
+            );
+
+            match self.hash_stored_data(&metadata) {
+                Ok(hash) => self.update_metadata(metadata.id, |metadata| {
+                    metadata.hash = Some(hash);
+                    metadata.version = METADATA_VERSION;
+                })?,
+                Err(err) => eprintln!("Failed to hash data of restore `{}` ({err})", metadata.id),
+            }
+        }
+
+        Ok(())
+    }
+}
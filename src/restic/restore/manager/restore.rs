@@ -1,142 +1,485 @@
-use super::{RestoreManager, RestoreMetadata};
+use super::{queue::Job, RestoreManager, RestoreMetadata, KEY_SALT_LEN, METADATA_VERSION};
 use crate::{
     restic::{
-        repository::Snapshot,
+        repository::{Entry, EntryKind, Snapshot},
         restore::{
+            cipher::{DecryptReader, EncryptWriter},
             destination::{ArchiveDestination, FileDestination},
-            hash::HashWriter,
+            hash::hash_data,
+            plan::{RestoreMode, RestorePlan},
             progress::{ProgressTracker, Status},
-            RestoreContent, RestoreId,
+            stream::{self, DataReceiver, TeeWriter},
+            ContentId, FailureKind, RestoreContent, RestoreFailure, RestoreFilter, RestoreId,
+            RestoreOptions,
         },
     },
-    Result,
+    Error, Result,
 };
 use chrono::Utc;
+use rand::{rngs::OsRng, RngCore};
 use std::{
-    fs::File,
-    io::{self, BufWriter, Write},
-    path::{Path, PathBuf},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
     thread,
 };
+use tokio::task::spawn_blocking;
 
 impl RestoreManager {
     fn set_metadata(&self, id: RestoreId, metadata: &RestoreMetadata) -> io::Result<()> {
-        let meta_file = File::options()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(self.meta_path(id))?;
+        self.write_json(&Self::meta_key(id), metadata)?;
+        self.index_metadata(metadata);
 
-        serde_json::to_writer(meta_file, metadata)?;
+        Ok(())
+    }
+
+    pub(super) fn delete_metadata(&self, id: RestoreId) -> io::Result<()> {
+        self.storage.delete(&Self::meta_key(id))?;
+        self.data_index.remove(id);
 
         Ok(())
     }
 
-    fn restore_task(
+    /// Applies a modification to the stored metadata while preventing concurrent updates from being lost.
+    pub(super) fn update_metadata(
         &self,
         id: RestoreId,
-        snapshot: Snapshot,
-        source: PathBuf,
-        progress: &mut ProgressTracker,
-    ) -> Result<()> {
-        let lock = self.purge_lock.read().expect("purge lock poisoned");
-        let plan = snapshot.restore(source)?;
-        let source = plan.source().path.clone();
+        update: impl FnOnce(&mut RestoreMetadata),
+    ) -> io::Result<()> {
+        let lock = self.metadata_lock.lock().expect("metadata lock poisoned");
+
+        let mut metadata = self.fetch_metadata(id)?;
+        update(&mut metadata);
+
+        // Outdated hashes are migrated in the background, which needs to recognise them after a restart
+        if metadata.version >= 2 || metadata.hash.is_none() {
+            metadata.version = METADATA_VERSION;
+        }
+
+        self.set_metadata(id, &metadata)?;
+
+        drop(lock);
+
+        Ok(())
+    }
+
+    /// Finds a completed restore of the given content whose data can be shared.
+    fn find_artifact(&self, content_id: ContentId) -> io::Result<Option<RestoreMetadata>> {
+        let data_key = Self::content_data_key(content_id);
+
+        for (id, reference) in self.data_index.referencing(&data_key) {
+            if !reference.complete {
+                continue;
+            }
+
+            let Ok(metadata) = self.fetch_metadata(id) else {
+                continue;
+            };
+
+            if metadata.hash.is_some() && self.storage.exists(&data_key)? {
+                return Ok(Some(metadata));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Points the restore at existing data of the same content.
+    fn link_artifact(&self, id: RestoreId, artifact: &RestoreMetadata) -> io::Result<()> {
+        self.update_metadata(id, |metadata| {
+            metadata.hash = artifact.hash;
+            metadata.key_salt = artifact.key_salt.clone();
+            metadata.errors = artifact.errors;
+        })
+    }
+
+    /// Plans the restore of the source, which is limited to the changes since the base snapshot if there is one.
+    fn plan<'s>(
+        snapshot: &'s Snapshot,
+        base: Option<&Snapshot>,
+        source: &Path,
+        filter: &RestoreFilter,
+    ) -> Result<RestorePlan<'s, Box<dyn Iterator<Item = Entry> + 's>>> {
+        Ok(match base {
+            Some(base) => snapshot.diff(base, source, filter)?.boxed(),
+            // Owned, as the plan would otherwise be tied to the borrowed path
+            None => snapshot.restore(source.to_owned(), filter)?.boxed(),
+        })
+    }
+
+    /// Plans the restore and records it, making it visible to users before its data is written.
+    /// Returns whether the data still has to be written, i.e. there is no data of the same content to share.
+    fn prepare(&self, job: &mut Job) -> Result<bool> {
+        let id = job.id;
+        let snapshot = &job.snapshot;
+        let content_id = ContentId::new(
+            snapshot,
+            &job.source,
+            job.layout,
+            &job.filter,
+            job.base.as_ref(),
+            job.manifest,
+        )?;
+
+        // Keeps the data which is about to be linked from being purged meanwhile
+        let _claim = self.data_claims.claim(Self::content_data_key(content_id));
+        let plan = Self::plan(snapshot, job.base.as_ref(), &job.source, &job.filter)?;
         let content = *plan.content();
-        let created_at = Utc::now();
+        let artifact = self.find_artifact(content_id)?;
+
+        // Archives are compressed, hence the content size is an upper estimate for most data
+        if artifact.is_none() {
+            self.reserve(id, content.size())?;
+        }
+
+        // Data served straight from storage can not be decrypted on the way
+        let salt = (!self.storage.serves_directly()).then(|| {
+            let mut salt = [0; KEY_SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            salt
+        });
 
         self.set_metadata(
             id,
             &RestoreMetadata {
+                version: METADATA_VERSION,
                 id,
-                source: source.clone(),
+                source: plan.source().path.clone(),
                 content,
+                layout: Some(job.layout),
+                filter: job.filter.clone(),
+                base_snapshot: job.base.as_ref().map(|base| base.id().as_str().to_string()),
+                manifest: job.manifest,
                 hash: None,
-                created_at,
+                created_at: Utc::now(),
+                repository: Some(snapshot.repo().name().to_string()),
+                snapshot: Some(snapshot.id().as_str().to_string()),
+                expires_at: None,
+                downloads: 0,
+                last_access: None,
+                pinned: false,
+                key_salt: salt.map(hex::encode),
+                content_id: Some(content_id),
+                failure: None,
+                errors: 0,
+                owner: job.owner.clone(),
             },
         )?;
 
-        progress.set_state(content.into());
+        job.progress.set_state(content.into());
+        drop(plan);
 
-        let mut writer = BufWriter::new(HashWriter::new(File::create_new(self.data_path(id))?));
+        if let Some(artifact) = artifact {
+            self.link_artifact(id, &artifact)?;
+            return Ok(false);
+        }
 
-        match plan.content() {
-            RestoreContent::File { .. } => {
-                plan.execute(FileDestination::new(&mut writer, progress)?)?
-            }
+        Ok(true)
+    }
+
+    /// Writes the data of a queued restore, returning the number of entries which had to be skipped.
+    fn execute(&self, job: &mut Job) -> Result<u64> {
+        let metadata = self.fetch_metadata(job.id)?;
+        let content_id = metadata.content_id.ok_or(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "queued restore lacks a content id",
+        ))?;
+        let data_key = Self::data_key(&metadata);
+        let _claim = self.data_claims.claim(data_key.clone());
+
+        // Another restore of the same content might be writing the data right now
+        let artifact_lock = self.artifact_locks.get(content_id);
+        let artifact_guard = artifact_lock.lock().expect("artifact lock poisoned");
+
+        if let Some(artifact) = self.find_artifact(content_id)? {
+            self.link_artifact(job.id, &artifact)?;
+            return Ok(artifact.errors);
+        }
+
+        job.progress.set_status(Status::Restoring);
+
+        // Left behind by a previous attempt which failed
+        self.storage.delete(&data_key).ok();
+
+        let plan = Self::plan(&job.snapshot, job.base.as_ref(), &job.source, &job.filter)?;
+        let progress = &mut job.progress;
+        let file = EncryptWriter::new(
+            self.storage.create(&data_key)?,
+            self.metadata_cipher(&metadata)?,
+        );
+        let mut writer = BufWriter::new(TeeWriter::new(file, &mut job.stream));
+
+        // Archives are still worth downloading with some entries missing, a single file is not
+        let skipped = match plan.content() {
+            RestoreContent::File { .. } => plan.execute(
+                FileDestination::new(&mut writer, progress)?,
+                RestoreMode::Strict,
+            )?,
             RestoreContent::Archive { .. } => {
-                let path_base = if self.keep_full_paths {
-                    PathBuf::new()
-                } else {
-                    plan.source.path.clone()
-                };
+                let path_base = job.layout.base(&plan.source.path);
+                let folder = job.layout.folder(&job.snapshot);
+                let info = job.manifest.then(|| plan.info());
+                let mut destination =
+                    ArchiveDestination::new(&mut writer, progress, path_base, folder)?;
+
+                if let Some(info) = info {
+                    destination = destination.with_manifest(info);
+                }
 
-                plan.execute(ArchiveDestination::new(&mut writer, progress, path_base)?)?
+                plan.execute(destination, RestoreMode::Tolerant)?
             }
         };
+        let errors = skipped.len() as u64;
 
-        let (hash, mut file) = writer.into_inner().map_err(|e| e.into_error())?.finalize();
+        let mut file = writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .into_inner()
+            .into_inner();
         file.flush()?;
 
-        self.set_metadata(
-            id,
-            &RestoreMetadata {
-                id,
-                source,
-                content,
-                hash: Some(hash),
-                created_at,
-            },
-        )?;
+        // Hashes what ended up in storage rather than what was written, catching data damaged on its way
+        file.seek(SeekFrom::Start(0))?;
+        let hash = hash_data(DecryptReader::new(
+            &mut file,
+            self.metadata_cipher(&metadata)?,
+            0,
+        ))?;
 
-        drop(lock);
+        self.storage.persist(&data_key, file)?;
 
-        Ok(())
-    }
+        self.update_metadata(job.id, |metadata| {
+            metadata.hash = Some(hash);
+            metadata.errors = errors;
+        })?;
 
-    pub async fn restore(&self, snapshot: Snapshot, source: impl AsRef<Path>) -> RestoreId {
-        let id = RestoreId::new(&snapshot, &source);
+        drop(artifact_guard);
 
-        // Short-circuit if there is already a matching restore
-        if tokio::fs::try_exists(self.meta_path(id))
-            .await
-            .unwrap_or_default()
-        {
-            return id;
-        }
+        Ok(errors)
+    }
 
-        let source = source.as_ref().to_owned();
-        let mut progress = ProgressTracker::new();
-        let manager = self.clone();
+    /// Records the outcome of a job and stops tracking its progress.
+    fn finish(&self, mut job: Job, result: Result<u64>) {
+        match result {
+            Ok(errors) => job.progress.complete(errors),
+            Err(err) => {
+                // Writes fail with an I/O error once cancelled, which is not worth reporting
+                let failure = if job.progress.is_cancelled() {
+                    job.progress.set_status(Status::Cancelled);
+                    RestoreFailure::new(FailureKind::Cancelled, "Cancelled by the user")
+                } else {
+                    eprintln!("Restore failed: {err:?}");
+                    let failure = RestoreFailure::from(&err);
+                    job.progress.fail(failure.clone());
+                    failure
+                };
 
-        let progress_handle = progress.handle();
+                if let Err(err) = self.fail(job.id, failure) {
+                    eprintln!("Failed to record failure of restore `{}`: {err:?}", job.id);
+                }
+            }
+        }
 
+        self.release(job.id);
         self.progress
             .lock()
             .expect("progress map poisoned")
-            .insert(id, progress_handle.clone());
+            .remove(&job.id);
+    }
+
+    /// Removes a restore from the queue before a worker picks it up.
+    pub(super) fn dequeue(&self, id: RestoreId) {
+        if let Some(job) = self.queue.remove(id) {
+            self.finish(job, Err(Error::Cancelled));
+        }
+    }
 
-        thread::spawn(move || {
-            let result = manager.restore_task(id, snapshot, source, &mut progress);
+    pub(super) fn spawn_workers(&self, count: usize) {
+        for _ in 0..count {
+            let manager = self.clone();
 
-            match result {
-                Ok(_) => progress.set_status(Status::Completed),
-                Err(err) => {
-                    eprintln!("Restore failed: {err:?}");
-                    progress.set_status(Status::Failed);
+            // TODO Stop the threads once the last (other) manager instance has vanished, like the purge thread
+            thread::spawn(move || loop {
+                let mut job = manager.queue.next();
+                let result = manager.execute(&mut job);
+
+                // Aborts the download instead of letting it end as if the data was complete
+                if let (Err(err), Some(stream)) = (&result, job.stream.take()) {
+                    stream.abort(io::Error::other(err.to_string()));
                 }
+
+                manager.queue.done(&job);
+                manager.finish(job, result);
+            });
+        }
+    }
+
+    /// Starts tracking the progress of the restore, unless it is tracked already or exists.
+    /// Returns whether it is tracked on behalf of this call. Callers have to hold the restore lock.
+    pub(super) fn track(&self, id: RestoreId, progress: &ProgressTracker) -> bool {
+        // Short-circuit if there is already a matching restore, unless it has to be retried
+        if self
+            .fetch_metadata(id)
+            .is_ok_and(|metadata| metadata.failure.is_none())
+        {
+            return false;
+        }
+
+        let mut tracked = self.progress.lock().expect("progress map poisoned");
+
+        if tracked.contains_key(&id) {
+            return false;
+        }
+
+        tracked.insert(id, progress.handle());
+        true
+    }
+
+    /// Returns whether the job has been queued to write the data, as opposed to the restore already existing.
+    fn enqueue(&self, mut job: Job) -> Result<bool> {
+        let id = job.id;
+
+        // Concurrent requests for the same restore wait here and attach to the first one
+        let restore_lock = self.restore_locks.get(id);
+        let _guard = restore_lock.lock().expect("restore lock poisoned");
+
+        if !self.track(id, &job.progress) {
+            return Ok(false);
+        }
+
+        match self.prepare(&mut job) {
+            Ok(true) => {
+                self.queue.push(job);
+                Ok(true)
             }
+            // Either shares existing data or is reported to the requester, as it never got started
+            result => {
+                match &result {
+                    Ok(_) => job.progress.set_status(Status::Completed),
+                    Err(err) => job.progress.fail(err.into()),
+                }
 
-            manager
-                .progress
-                .lock()
-                .expect("progress map poisoned")
-                .remove(&id);
-        });
+                self.release(id);
+                self.progress
+                    .lock()
+                    .expect("progress map poisoned")
+                    .remove(&id);
+
+                result.map(|_| false)
+            }
+        }
+    }
+
+    /// Describes the restore of the source path with the given options, which identify it.
+    fn job(
+        &self,
+        snapshot: Snapshot,
+        source: &Path,
+        options: RestoreOptions,
+        owner: Option<String>,
+    ) -> Result<Job> {
+        let layout = options.layout.unwrap_or(self.default_layout);
+
+        // Filters select entries of directories, a single file is restored regardless
+        let filter = match snapshot.entry(source)?.kind {
+            EntryKind::File => RestoreFilter::default(),
+            EntryKind::Directory => options.filter,
+        };
+
+        Ok(Job {
+            id: RestoreId::new(
+                &snapshot,
+                source,
+                layout,
+                &filter,
+                options.base.as_ref(),
+                options.manifest,
+            ),
+            snapshot,
+            source: source.to_owned(),
+            layout,
+            filter,
+            base: options.base,
+            manifest: options.manifest,
+            owner,
+            progress: ProgressTracker::new(),
+            stream: None,
+        })
+    }
+
+    /// Queues the restore of the source path, which is written in the background.
+    /// Archives are laid out as configured for the server unless the options choose another layout.
+    /// Fails if the restore is rejected, e.g. because it does not fit into the available storage.
+    pub async fn restore(
+        &self,
+        snapshot: Snapshot,
+        source: impl AsRef<Path>,
+        options: RestoreOptions,
+        owner: Option<String>,
+    ) -> Result<RestoreId> {
+        let source = source.as_ref().to_owned();
+        let manager = self.clone();
+
+        spawn_blocking(move || {
+            let job = manager.job(snapshot, &source, options, owner)?;
+            let id = job.id;
+
+            manager.enqueue(job).map(|_| id)
+        })
+        .await
+        .map_err(io::Error::from)?
+    }
+
+    /// Queues the restore like [`RestoreManager::restore`], handing its data to the returned receiver while it is written.
+    /// Nothing is received if the data is not written on behalf of this call, e.g. because the restore already exists.
+    pub async fn stream(
+        &self,
+        snapshot: Snapshot,
+        source: impl AsRef<Path>,
+        options: RestoreOptions,
+        owner: Option<String>,
+    ) -> Result<(RestoreId, Option<DataReceiver>)> {
+        let (sender, receiver) = stream::channel();
+        let source = source.as_ref().to_owned();
+        let manager = self.clone();
+
+        let (id, queued) = spawn_blocking(move || {
+            let mut job = manager.job(snapshot, &source, options, owner)?;
+            job.stream = Some(sender);
+
+            let id = job.id;
 
-        // Wait for the first progress update so the user does not see a "not found" screen
-        _ = progress_handle.subscribe().recv().await;
+            manager.enqueue(job).map(|queued| (id, queued))
+        })
+        .await
+        .map_err(io::Error::from)??;
+
+        Ok((id, queued.then_some(receiver)))
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn attach_identical_requests_to_a_single_job() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = RestoreManager::for_tests(root.path());
+        let id = RestoreId::try_from("01".repeat(32)).unwrap();
+
+        let tracked = thread::scope(|scope| {
+            let requests = (0..8)
+                .map(|_| scope.spawn(|| manager.track(id, &ProgressTracker::new())))
+                .collect::<Vec<_>>();
+
+            requests
+                .into_iter()
+                .map(|request| request.join().unwrap())
+                .filter(|&tracked| tracked)
+                .count()
+        });
 
-        id
+        assert_eq!(tracked, 1);
+        assert!(manager.progress(id).is_ok());
     }
 }
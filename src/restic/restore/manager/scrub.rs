@@ -0,0 +1,115 @@
+use super::{RestoreManager, RestoreMetadata, META_DIR};
+use crate::restic::restore::{cipher::DecryptReader, hash::hash_data, FailureKind, RestoreFailure};
+use std::{
+    collections::HashSet,
+    io,
+    thread::{self, sleep},
+    time::Duration,
+};
+
+/// Where data which no longer matches its hash is kept for inspection
+const QUARANTINE_DIR: &str = "quarantine";
+
+/// Time between verifications of all stored data, which is costly for large restores
+const SCRUB_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+impl RestoreManager {
+    /// Hashes the data of a completed restore as it would be downloaded.
+    pub(super) fn hash_stored_data(&self, metadata: &RestoreMetadata) -> io::Result<blake3::Hash> {
+        let reader = self.storage.reader(&Self::data_key(metadata))?;
+        hash_data(DecryptReader::new(
+            reader,
+            self.metadata_cipher(metadata)?,
+            0,
+        ))
+    }
+
+    /// Re-hashes all stored data and quarantines data which no longer matches its hash,
+    /// failing the restores which reference it.
+    pub(super) fn scrub(&self) -> io::Result<()> {
+        let mut verified = HashSet::new();
+
+        for key in self.enumerate(META_DIR, "json")? {
+            let Ok(metadata) = self.read_json::<RestoreMetadata>(&key) else {
+                continue;
+            };
+
+            let Some(expected) = metadata.hash else {
+                continue;
+            };
+
+            // Hashes of earlier versions do not cover the data as it is downloaded until migrated
+            if metadata.version < 2 {
+                continue;
+            }
+
+            // Restores sharing their data only need to be checked once
+            let data_key = Self::data_key(&metadata);
+
+            if !verified.insert(data_key.clone()) {
+                continue;
+            }
+
+            match self.hash_stored_data(&metadata) {
+                Ok(hash) if hash == expected => {}
+                Ok(hash) => {
+                    eprintln!("Data at {data_key:?} does not match its hash (expected {expected}, got {hash})");
+                    self.quarantine(&data_key)?;
+                }
+                // Data might have been removed in the meantime
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => eprintln!("Failed to verify data at {data_key:?} ({err})"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn quarantine(&self, data_key: &str) -> io::Result<()> {
+        let file_name = data_key.rsplit('/').next().unwrap_or(data_key);
+        let quarantine_key = format!("{QUARANTINE_DIR}/{file_name}");
+
+        // Restores about to link the data would otherwise end up with a dangling reference
+        let quarantined = self.data_claims.with_unclaimed(&data_key.to_string(), || {
+            eprintln!("Moving data at {data_key:?} to {quarantine_key:?}");
+            self.storage.rename(data_key, &quarantine_key)
+        });
+
+        if quarantined.transpose()?.is_none() {
+            return Ok(());
+        }
+
+        for (id, reference) in self.data_index.referencing(data_key) {
+            if reference.complete {
+                let failure = RestoreFailure::new(
+                    FailureKind::Corrupted,
+                    "The restored data no longer matches its checksum",
+                );
+
+                self.fail(id, failure)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verifies the stored data on its own thread, as reading all of it takes much longer than purging.
+    pub(super) fn schedule_scrub(&self) {
+        let manager = self.clone();
+
+        // TODO Stop the thread once the last (other) manager instance has vanished
+        thread::spawn(move || {
+            if let Err(err) = manager.migrate_hashes() {
+                eprintln!("Hashing data of earlier versions failed: {err:?}");
+            }
+
+            loop {
+                sleep(SCRUB_INTERVAL);
+
+                if let Err(err) = manager.scrub() {
+                    eprintln!("Verification of restore data failed: {err:?}");
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,218 @@
+use super::{RestoreManager, SHARE_DIR};
+use crate::{
+    crypto::Key,
+    restic::restore::{PolicyViolation, RestoreId, Share, SharePolicy, ShareToken},
+};
+use chrono::Utc;
+use std::{cmp::Reverse, convert::Infallible, io, time::Duration};
+use tokio::task::spawn_blocking;
+
+const CONTEXT_RESUME_TICKET: &str = "restic-dl 2026-10 share resume ticket";
+const CONTEXT_ACCESS_TICKET: &str = "restic-dl 2026-10 share access ticket";
+
+/// Time during which tickets handed out for a share are accepted
+pub const TICKET_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+impl RestoreManager {
+    fn fetch_share(&self, token: ShareToken) -> io::Result<Share> {
+        self.read_json(&Self::share_key(token))
+    }
+
+    fn set_share(&self, share: &Share) -> io::Result<()> {
+        self.write_json(&Self::share_key(share.token), share)
+    }
+
+    fn update_share(
+        &self,
+        token: ShareToken,
+        update: impl FnOnce(&mut Share),
+    ) -> io::Result<Share> {
+        let result = self.try_update_share(token, |share| {
+            update(share);
+            Ok::<_, Infallible>(())
+        })?;
+
+        Ok(result.unwrap_or_else(|never| match never {}))
+    }
+
+    /// Updates the share unless `update` fails, in which case the record is left as it was.
+    fn try_update_share<E>(
+        &self,
+        token: ShareToken,
+        update: impl FnOnce(&mut Share) -> Result<(), E>,
+    ) -> io::Result<Result<Share, E>> {
+        let lock = self.metadata_lock.lock().expect("metadata lock poisoned");
+
+        let mut share = self.fetch_share(token)?;
+
+        if let Err(err) = update(&mut share) {
+            return Ok(Err(err));
+        }
+
+        self.set_share(&share)?;
+
+        drop(lock);
+
+        Ok(Ok(share))
+    }
+
+    pub(super) fn enumerate_shares(&self) -> io::Result<Vec<Share>> {
+        let mut shares = Vec::new();
+
+        for key in self.enumerate(SHARE_DIR, "json")? {
+            match self.read_json::<Share>(&key) {
+                Ok(share) => shares.push(share),
+                Err(err) => {
+                    eprintln!("Failed to read share at {key:?} ({err}), it will be removed");
+                    self.storage.delete(&key).ok();
+                }
+            }
+        }
+
+        Ok(shares)
+    }
+
+    /// Creates a new, unrestricted share link for an existing restore.
+    pub async fn create_share(
+        &self,
+        id: RestoreId,
+        created_by: Option<String>,
+    ) -> io::Result<Share> {
+        let manager = self.clone();
+
+        spawn_blocking(move || {
+            // Make sure we do not hand out links to restores which do not exist
+            manager.fetch_metadata(id)?;
+
+            let share = Share::new(id, created_by);
+            manager.set_share(&share)?;
+
+            Ok(share)
+        })
+        .await?
+    }
+
+    pub async fn share(&self, token: ShareToken) -> io::Result<Share> {
+        let manager = self.clone();
+        spawn_blocking(move || manager.fetch_share(token)).await?
+    }
+
+    /// Lists all shares of a restore, most recent first.
+    pub async fn shares(&self, id: RestoreId) -> io::Result<Vec<Share>> {
+        let manager = self.clone();
+        let mut shares = spawn_blocking(move || manager.enumerate_shares()).await??;
+
+        shares.retain(|share| share.restore == id);
+        shares.sort_by_key(|share| Reverse(share.created_at));
+
+        Ok(shares)
+    }
+
+    pub async fn configure_share(
+        &self,
+        token: ShareToken,
+        policy: SharePolicy,
+        message: Option<String>,
+    ) -> io::Result<Share> {
+        let manager = self.clone();
+
+        spawn_blocking(move || {
+            manager.update_share(token, |share| {
+                share.policy = policy;
+                share.message = message;
+            })
+        })
+        .await?
+    }
+
+    /// Counts a download through the share and records the time of access, unless the
+    /// share does not permit another download by now.
+    ///
+    /// The limits are checked while holding the metadata lock so that concurrent downloads
+    /// cannot exceed them.
+    pub async fn record_share_access(
+        &self,
+        token: ShareToken,
+    ) -> io::Result<Result<Share, PolicyViolation>> {
+        let manager = self.clone();
+
+        spawn_blocking(move || {
+            let share = manager.try_update_share(token, |share| {
+                share.check_download()?;
+                share.downloads += 1;
+                share.last_access = Some(Utc::now());
+
+                Ok(())
+            })?;
+
+            if let Ok(share) = &share {
+                manager.record_access_sync(share.restore)?;
+            }
+
+            Ok(share)
+        })
+        .await?
+    }
+
+    /// Revokes the share while keeping its record around for auditing.
+    pub async fn revoke_share(&self, token: ShareToken) -> io::Result<Share> {
+        let manager = self.clone();
+        spawn_blocking(move || manager.revoke_share_sync(token)).await?
+    }
+
+    pub(super) fn revoke_share_sync(&self, token: ShareToken) -> io::Result<Share> {
+        self.update_share(token, |share| {
+            share.revoked_at.get_or_insert_with(Utc::now);
+        })
+    }
+
+    /// Ticket handed out along with a counted download through the share, which permits
+    /// resuming that download without counting it again.
+    pub fn resume_ticket(&self, token: ShareToken) -> String {
+        self.ticket(CONTEXT_RESUME_TICKET, token)
+    }
+
+    pub fn verify_resume_ticket(&self, token: ShareToken, ticket: &str) -> bool {
+        self.verify_ticket(CONTEXT_RESUME_TICKET, token, ticket)
+    }
+
+    /// Ticket handed out once the password of the share has been verified, for requests
+    /// which cannot carry the password, such as following the progress of the restore.
+    pub fn access_ticket(&self, token: ShareToken) -> String {
+        self.ticket(CONTEXT_ACCESS_TICKET, token)
+    }
+
+    pub fn verify_access_ticket(&self, token: ShareToken, ticket: &str) -> bool {
+        self.verify_ticket(CONTEXT_ACCESS_TICKET, token, ticket)
+    }
+
+    fn ticket(&self, context: &str, token: ShareToken) -> String {
+        let issued_at = Utc::now().timestamp();
+        let mac = self.ticket_mac(context, token, issued_at);
+
+        format!("{issued_at}.{}", mac.to_hex())
+    }
+
+    /// Whether the ticket has been handed out for the share within the [`TICKET_LIFETIME`].
+    fn verify_ticket(&self, context: &str, token: ShareToken, ticket: &str) -> bool {
+        let Some((issued_at, mac)) = ticket.split_once('.') else {
+            return false;
+        };
+
+        let (Ok(issued_at), Ok(mac)) = (issued_at.parse::<i64>(), blake3::Hash::from_hex(mac))
+        else {
+            return false;
+        };
+
+        let age = Utc::now().timestamp().saturating_sub(issued_at);
+        let is_recent = (0..TICKET_LIFETIME.as_secs() as i64).contains(&age);
+
+        // Hashes are compared in constant time
+        is_recent && mac == self.ticket_mac(context, token, issued_at)
+    }
+
+    fn ticket_mac(&self, context: &str, token: ShareToken, issued_at: i64) -> blake3::Hash {
+        let key = Key::derive(context, &[self.secret.as_bytes()]);
+        blake3::keyed_hash(key.as_bytes(), format!("{token}.{issued_at}").as_bytes())
+    }
+}
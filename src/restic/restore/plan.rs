@@ -1,32 +1,105 @@
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
-use super::RestoreContent;
+use super::{RestoreContent, RestoreFailure, RestoreFilter};
 use crate::{
     restic::repository::{Entry, EntryKind, Snapshot},
-    restic::restore::destination::RestoreDestination,
-    Result,
+    restic::restore::destination::{FileWriter, RestoreDestination},
+    Error, Result,
 };
 
+/// Name of the report listing entries which were skipped by a tolerant restore
+const ERROR_REPORT_NAME: &str = "ERRORS.txt";
+
+/// How a restore deals with entries which can not be read from the repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreMode {
+    /// Fails the whole restore
+    Strict,
+    /// Skips the entry and lists it in an error report added to the restore
+    Tolerant,
+}
+
 pub struct RestorePlan<'s, Entries: Iterator<Item = Entry>> {
     pub(super) snapshot: &'s Snapshot,
     pub(super) entries: Entries,
 
     pub(super) source: Entry,
     pub(super) content: RestoreContent,
+    /// Text files describing the restore, added after its entries
+    pub(super) reports: Vec<(&'static str, String)>,
 }
 
 impl<'s, Entries: Iterator<Item = Entry>> RestorePlan<'s, Entries> {
-    pub fn execute(self, mut destination: impl RestoreDestination) -> Result<()> {
+    /// Writes all entries to the destination, returning those which were skipped in tolerant mode.
+    pub fn execute(
+        self,
+        mut destination: impl RestoreDestination,
+        mode: RestoreMode,
+    ) -> Result<Vec<RestoreFailure>> {
+        let mut skipped = Vec::new();
+
         for entry in self.entries {
-            match entry.kind {
-                EntryKind::Directory => {
-                    destination.add_dir(entry.path)?;
-                }
-                EntryKind::File => {
-                    let node = self.snapshot.node(&entry.path)?;
-                    let mut writer = destination.add_file(entry.path)?;
-                    self.snapshot.repo.dump(&node, &mut writer)?;
+            if destination.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            let path = entry.path.clone();
+            let result = Self::restore_entry(self.snapshot, &mut destination, entry)
+                .map_err(|err| err.at(path));
+
+            match result {
+                // Only unreadable data is skipped, a broken destination would fail every following entry as well
+                Err(err)
+                    if mode == RestoreMode::Tolerant
+                        && matches!(err.root(), Error::BackendError(_)) =>
+                {
+                    eprintln!("Skipping entry of tolerant restore: {err}");
+                    skipped.push(RestoreFailure::from(&err));
                 }
+                result => result?,
+            }
+        }
+
+        for (name, contents) in &self.reports {
+            destination.add_report(name, contents)?;
+        }
+
+        if !skipped.is_empty() {
+            destination.add_report(ERROR_REPORT_NAME, &Self::error_report(&skipped))?;
+        }
+
+        destination.finish()?;
+
+        Ok(skipped)
+    }
+
+    fn error_report(skipped: &[RestoreFailure]) -> String {
+        let mut report = String::from(
+            "The following entries could not be read from the repository.\n\
+             They are missing from this archive or their contents are incomplete.\n\n",
+        );
+
+        for failure in skipped {
+            report.push_str(&format!("{failure}\n"));
+        }
+
+        report
+    }
+
+    fn restore_entry(
+        snapshot: &Snapshot,
+        destination: &mut impl RestoreDestination,
+        entry: Entry,
+    ) -> Result<()> {
+        match entry.kind {
+            EntryKind::Directory => {
+                destination.add_dir(entry.path)?;
+            }
+            EntryKind::File => {
+                let node = snapshot.node(&entry.path)?;
+                let mut writer = destination.add_file(entry.path)?;
+                snapshot.repo.dump(&node, &mut writer)?;
+                writer.finish()?;
             }
         }
 
@@ -40,33 +113,71 @@ impl<'s, Entries: Iterator<Item = Entry>> RestorePlan<'s, Entries> {
     pub fn source(&self) -> &Entry {
         &self.source
     }
+
+    /// Describes where the restored entries come from, for archives which describe themselves.
+    pub fn info(&self) -> String {
+        let snapshot = self.snapshot.info();
+
+        format!(
+            "Repository: {}\nSnapshot: {}\nHost: {}\nTime: {}\nSource: {}\n",
+            self.snapshot.repo().name(),
+            self.snapshot.id().as_str(),
+            snapshot.hostname,
+            snapshot.time.to_rfc3339(),
+            self.source.path.display()
+        )
+    }
+
+    /// Erases the type of the entries, so that plans of different kinds can be handled alike.
+    pub fn boxed(self) -> RestorePlan<'s, Box<dyn Iterator<Item = Entry> + 's>>
+    where
+        Entries: 's,
+    {
+        RestorePlan {
+            snapshot: self.snapshot,
+            entries: Box::new(self.entries),
+            source: self.source,
+            content: self.content,
+            reports: self.reports,
+        }
+    }
+}
+
+/// Totals of an archive containing the entries.
+pub(super) fn archive_content(entries: impl Iterator<Item = Entry>) -> RestoreContent {
+    let (size, files, directories) =
+        entries.fold((0, 0, 0), |(size, files, directories), entry| {
+            match entry.kind {
+                EntryKind::File => (size + entry.size, files + 1, directories),
+                EntryKind::Directory => (size + entry.size, files, directories + 1),
+            }
+        });
+
+    RestoreContent::Archive {
+        size,
+        files,
+        directories,
+    }
 }
 
 impl Snapshot {
+    /// Plans the restore of the source, leaving out the entries of a directory which the filter rejects.
     pub fn restore(
         &self,
         source: impl AsRef<Path>,
+        filter: &RestoreFilter,
     ) -> Result<RestorePlan<impl Iterator<Item = Entry> + '_>> {
         let target = self.entry(&source)?;
-        let entries = self.enumerate(source, true)?;
+        let matcher = Arc::new(filter.matcher()?);
+        let base = source.as_ref().to_owned();
+        let is_file = target.kind == EntryKind::File;
+        let entries = self.enumerate(source, true)?.filter(move |entry| {
+            is_file || matcher.matches(entry, entry.path.strip_prefix(&base).unwrap_or(&entry.path))
+        });
 
         let content = match target.kind {
             EntryKind::File => RestoreContent::File { size: target.size },
-            EntryKind::Directory => {
-                let (size, files, directories) = entries.clone().fold(
-                    (0, 0, 0),
-                    |(size, files, directories), entry| match entry.kind {
-                        EntryKind::File => (size + entry.size, files + 1, directories),
-                        EntryKind::Directory => (size + entry.size, files, directories + 1),
-                    },
-                );
-
-                RestoreContent::Archive {
-                    size,
-                    files,
-                    directories,
-                }
-            }
+            EntryKind::Directory => archive_content(entries.clone()),
         };
 
         Ok(RestorePlan {
@@ -75,6 +186,7 @@ impl Snapshot {
 
             source: target,
             content,
+            reports: Vec::new(),
         })
     }
 }
@@ -0,0 +1,156 @@
+use argon2::{
+    password_hash::{PasswordHashString, SaltString},
+    Argon2, PasswordHasher, PasswordVerifier,
+};
+use chrono::{DateTime, Utc};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+/// Restrictions put on a restore by the person sharing it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SharePolicy {
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_downloads: Option<u64>,
+    /// PHC string of the password required for downloading
+    password_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+    Revoked,
+    Expired,
+    Exhausted,
+    PasswordRequired,
+    PasswordInvalid,
+}
+
+impl SharePolicy {
+    pub async fn new(
+        expires_at: Option<DateTime<Utc>>,
+        max_downloads: Option<u64>,
+        password: Option<String>,
+    ) -> Self {
+        let password = password.filter(|p| !p.is_empty());
+
+        // Hashing is deliberately expensive and must not block the executor
+        let password_hash = match password {
+            Some(password) => Some(
+                spawn_blocking(move || {
+                    let salt = SaltString::generate(&mut OsRng);
+
+                    Argon2::default()
+                        .hash_password(password.as_bytes(), &salt)
+                        .expect("Failed to generate password hash")
+                        .to_string()
+                })
+                .await
+                .expect("Failed to generate password hash"),
+            ),
+            None => None,
+        };
+
+        Self {
+            expires_at,
+            max_downloads,
+            password_hash,
+        }
+    }
+
+    /// Creates a copy with different limits while retaining the password.
+    pub fn with_limits(
+        &self,
+        expires_at: Option<DateTime<Utc>>,
+        max_downloads: Option<u64>,
+    ) -> Self {
+        Self {
+            expires_at,
+            max_downloads,
+            password_hash: self.password_hash.clone(),
+        }
+    }
+
+    pub fn has_password(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// Verifies that the link has neither expired nor been used up given the number of
+    /// previous downloads.
+    pub fn check_limits(&self, downloads: u64) -> Result<(), PolicyViolation> {
+        self.check_expiry()?;
+
+        if self.max_downloads.is_some_and(|max| downloads >= max) {
+            return Err(PolicyViolation::Exhausted);
+        }
+
+        Ok(())
+    }
+
+    pub fn check_expiry(&self) -> Result<(), PolicyViolation> {
+        if self.expires_at.is_some_and(|expiry| expiry <= Utc::now()) {
+            return Err(PolicyViolation::Expired);
+        }
+
+        Ok(())
+    }
+
+    pub async fn verify_password(&self, password: Option<&str>) -> Result<(), PolicyViolation> {
+        let Some(hash) = self.password_hash.clone() else {
+            return Ok(());
+        };
+
+        let password = password
+            .ok_or(PolicyViolation::PasswordRequired)?
+            .to_string();
+
+        spawn_blocking(move || {
+            let hash =
+                PasswordHashString::new(&hash).map_err(|_| PolicyViolation::PasswordInvalid)?;
+
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash.password_hash())
+                .map_err(|_| PolicyViolation::PasswordInvalid)
+        })
+        .await
+        .unwrap_or(Err(PolicyViolation::PasswordInvalid))
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn permit_unrestricted_downloads() {
+        assert_eq!(SharePolicy::default().check_limits(u64::MAX), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn reject_expired_and_exhausted_links() {
+        let past = Utc::now() - TimeDelta::hours(1);
+        let future = Utc::now() + TimeDelta::hours(1);
+
+        let expired = SharePolicy::new(Some(past), None, None).await;
+        let valid = SharePolicy::new(Some(future), Some(2), None).await;
+
+        assert_eq!(expired.check_limits(0), Err(PolicyViolation::Expired));
+        assert_eq!(valid.check_limits(1), Ok(()));
+        assert_eq!(valid.check_limits(2), Err(PolicyViolation::Exhausted));
+    }
+
+    #[tokio::test]
+    async fn require_matching_password() {
+        let policy = SharePolicy::new(None, None, Some("hunter2".into())).await;
+
+        assert_eq!(
+            policy.verify_password(None).await,
+            Err(PolicyViolation::PasswordRequired)
+        );
+        assert_eq!(
+            policy.verify_password(Some("hunter3")).await,
+            Err(PolicyViolation::PasswordInvalid)
+        );
+        assert_eq!(policy.verify_password(Some("hunter2")).await, Ok(()));
+    }
+}
@@ -1,3 +1,5 @@
+use super::failure::RestoreFailure;
+
 mod count;
 mod update;
 mod writer;
@@ -12,21 +14,28 @@ pub struct ProgressVariable {
     pub total: u64,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Progress {
     pub data: ProgressVariable,
     pub files: Option<ProgressVariable>,
     pub directories: Option<ProgressVariable>,
     pub status: Status,
+    /// Entries which had to be skipped by a tolerant restore
+    pub errors: u64,
+    /// Explanation of why the restore failed, present once its status is [`Status::Failed`]
+    pub failure: Option<RestoreFailure>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Status {
     #[default]
     Collecting,
+    /// Waiting for a worker, at the given position within the queue starting at `1`
+    Queued(usize),
     Restoring,
     Completed,
     Failed,
+    Cancelled,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -1,16 +1,20 @@
-
 use tokio::sync::broadcast;
 
 use super::{count::ProgressCount, Progress, Status};
+use crate::restic::restore::failure::RestoreFailure;
 use std::{
     ops::AddAssign,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
 #[derive(Clone)]
 pub struct ProgressTracker {
     channel: broadcast::Sender<Progress>,
     state: Arc<Mutex<Progress>>,
+    cancelled: Arc<AtomicBool>,
 }
 
 #[derive(Clone)]
@@ -23,6 +27,7 @@ impl ProgressTracker {
         Self {
             channel: broadcast::channel(16).0,
             state: Arc::new(Mutex::new(Progress::default())),
+            cancelled: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -32,13 +37,33 @@ impl ProgressTracker {
     pub fn set_state(&mut self, new_state: Progress) {
         let mut state = self.state.lock().expect("progress mutex poisoned");
         *state = new_state;
-        _ = self.channel.send(new_state);
+        _ = self.channel.send(state.clone());
     }
 
     pub fn set_status(&mut self, status: Status) {
         let mut state = self.state.lock().expect("progress mutex poisoned");
         state.status = status;
-        _ = self.channel.send(*state);
+        _ = self.channel.send(state.clone());
+    }
+
+    /// Marks the restore as completed, albeit with the given number of entries skipped.
+    pub fn complete(&mut self, errors: u64) {
+        let mut state = self.state.lock().expect("progress mutex poisoned");
+        state.status = Status::Completed;
+        state.errors = errors;
+        _ = self.channel.send(state.clone());
+    }
+
+    pub fn fail(&mut self, failure: RestoreFailure) {
+        let mut state = self.state.lock().expect("progress mutex poisoned");
+        state.status = Status::Failed;
+        state.failure = Some(failure);
+        _ = self.channel.send(state.clone());
+    }
+
+    /// Whether the restore has been asked to stop, which it has to check for regularly.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
     }
 
     pub fn handle(&self) -> ProgressReceiver {
@@ -53,13 +78,22 @@ impl<C: Into<ProgressCount>> AddAssign<C> for ProgressTracker {
         let mut state = self.state.lock().expect("progress mutex poisoned");
         *state += count;
 
-        _ = self.channel.send(*state);
+        _ = self.channel.send(state.clone());
     }
 }
 
 impl ProgressReceiver {
     pub fn current(&self) -> Progress {
-        *self.0.state.lock().expect("progress mutex poisoned")
+        self.0
+            .state
+            .lock()
+            .expect("progress mutex poisoned")
+            .clone()
+    }
+
+    /// Asks the restore to stop at the next opportunity.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
     }
 
     pub fn subscribe(&self) -> ProgressSubscription {
@@ -26,6 +26,10 @@ impl<'p, W: Write> ProgressWriter<'p, W> {
 
 impl<'p, W: Write> Write for ProgressWriter<'p, W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.progress.is_cancelled() {
+            return Err(io::Error::other("restore cancelled"));
+        }
+
         let written = self.writer.write(buf)?;
         self.counter += written as u64;
 
@@ -0,0 +1,158 @@
+use super::{PolicyViolation, RestoreId, SharePolicy};
+use chrono::{DateTime, Utc};
+use hex::FromHex;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Random, unguessable identifier of a share which is used in public download links.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct ShareToken([u8; 32]);
+
+/// A single link through which a restore is made available to others.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    pub token: ShareToken,
+    pub restore: RestoreId,
+    pub policy: SharePolicy,
+    /// Note from the sharer which is shown to recipients
+    #[serde(default)]
+    pub message: Option<String>,
+    pub downloads: u64,
+    pub created_at: DateTime<Utc>,
+    /// Handle of the session which created the share
+    pub created_by: Option<String>,
+    pub last_access: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ShareToken {
+    pub fn new() -> Self {
+        let mut token = [0; 32];
+        thread_rng().fill(&mut token);
+        Self(token)
+    }
+}
+
+impl Share {
+    pub fn new(restore: RestoreId, created_by: Option<String>) -> Self {
+        Self {
+            token: ShareToken::new(),
+            restore,
+            policy: SharePolicy::default(),
+            message: None,
+            downloads: 0,
+            created_at: Utc::now(),
+            created_by,
+            last_access: None,
+            revoked_at: None,
+        }
+    }
+
+    /// Verifies that the share has not been revoked and permits another download.
+    pub async fn check(&self, password: Option<&str>) -> Result<(), PolicyViolation> {
+        self.check_download()?;
+        self.policy.verify_password(password).await
+    }
+
+    /// Verifies that the share permits resuming a download which has already been counted.
+    pub async fn check_resume(&self, password: Option<&str>) -> Result<(), PolicyViolation> {
+        self.check_revoked()?;
+        self.policy.check_expiry()?;
+        self.policy.verify_password(password).await
+    }
+
+    /// Verifies that the share permits following the progress of its restore, given whether
+    /// its password has been verified before.
+    pub fn check_progress(&self, is_unlocked: bool) -> Result<(), PolicyViolation> {
+        self.check_revoked()?;
+        self.policy.check_expiry()?;
+
+        match is_unlocked || !self.policy.has_password() {
+            true => Ok(()),
+            false => Err(PolicyViolation::PasswordRequired),
+        }
+    }
+
+    /// Verifies everything but the password, which is done before counting a download.
+    pub fn check_download(&self) -> Result<(), PolicyViolation> {
+        self.check_revoked()?;
+        self.policy.check_limits(self.downloads)
+    }
+
+    fn check_revoked(&self) -> Result<(), PolicyViolation> {
+        match self.revoked_at {
+            Some(_) => Err(PolicyViolation::Revoked),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for ShareToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl FromStr for ShareToken {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(<[u8; 32]>::from_hex(s)?))
+    }
+}
+
+impl From<ShareToken> for String {
+    fn from(token: ShareToken) -> Self {
+        token.to_string()
+    }
+}
+
+impl TryFrom<String> for ShareToken {
+    type Error = hex::FromHexError;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        hex.parse()
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn roundtrip_tokens() {
+        let token = ShareToken::new();
+
+        assert_eq!(token.to_string().parse::<ShareToken>(), Ok(token));
+        assert_ne!(token, ShareToken::new());
+    }
+
+    #[tokio::test]
+    async fn reject_revoked_shares() {
+        let id = RestoreId::try_from("00".repeat(32)).unwrap();
+        let mut share = Share::new(id, None);
+
+        assert_eq!(share.check(None).await, Ok(()));
+
+        share.revoked_at = Some(Utc::now());
+        assert_eq!(share.check(None).await, Err(PolicyViolation::Revoked));
+        assert_eq!(
+            share.check_resume(None).await,
+            Err(PolicyViolation::Revoked)
+        );
+    }
+
+    #[tokio::test]
+    async fn resume_counted_downloads() {
+        let id = RestoreId::try_from("00".repeat(32)).unwrap();
+        let mut share = Share::new(id, None);
+
+        share.policy = SharePolicy::new(None, Some(1), None).await;
+        share.downloads = 1;
+
+        assert_eq!(share.check(None).await, Err(PolicyViolation::Exhausted));
+        assert_eq!(share.check_resume(None).await, Ok(()));
+    }
+}
@@ -0,0 +1,88 @@
+use futures::future::BoxFuture;
+use std::{
+    fs::File,
+    io::{self, Read},
+    pin::Pin,
+    time::Duration,
+};
+use tokio::io::AsyncRead;
+
+mod local;
+mod s3;
+
+pub use local::LocalStorage;
+pub use s3::{S3Config, S3Storage};
+
+pub type DataReader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// Backend holding the records and data of restores.
+///
+/// Keys are relative paths like `meta/<id>.json`. Records are small documents which are always
+/// read and written in full. Data objects are large, need to be seekable while they are written
+/// and are therefore staged in a local file which is handed back to [`Storage::persist`].
+///
+/// Writes and persisted data objects have to be atomic: readers either see the previous or the
+/// complete new object, even if the server crashes halfway.
+pub trait Storage: Send + Sync {
+    fn read(&self, key: &str) -> io::Result<Vec<u8>>;
+
+    fn write(&self, key: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Removes the object, abandoning it if it is still being written.
+    fn delete(&self, key: &str) -> io::Result<()>;
+
+    /// Lists the keys of all objects within the given directory.
+    fn list(&self, directory: &str) -> io::Result<Vec<String>>;
+
+    fn size(&self, key: &str) -> io::Result<u64>;
+
+    fn exists(&self, key: &str) -> io::Result<bool> {
+        match self.size(key) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Creates the file in which a data object is written before it is persisted.
+    fn create(&self, key: &str) -> io::Result<File>;
+
+    fn persist(&self, key: &str, file: File) -> io::Result<()>;
+
+    /// Cleans up after writes which were interrupted by a crash, called before any other use.
+    fn recover(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn open(&self, key: &str) -> io::Result<Box<dyn StoredData>>;
+
+    /// Reads a data object from a background thread, e.g. to verify it.
+    fn reader(&self, key: &str) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Moves a data object to another key.
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut file = self.create(to)?;
+        io::copy(&mut self.reader(from)?, &mut file)?;
+        self.persist(to, file)?;
+        self.delete(from)
+    }
+
+    /// Whether clients download data objects straight from the backend.
+    /// Such data can not be decrypted by us and is therefore stored in plain text.
+    fn serves_directly(&self) -> bool {
+        false
+    }
+
+    /// Creates a time-limited URL through which the data object can be downloaded directly.
+    fn presign(&self, _key: &str, _file_name: &str, _lifetime: Duration) -> Option<String> {
+        None
+    }
+}
+
+/// Handle to a persisted data object.
+pub trait StoredData: Send {
+    fn size(&self) -> u64;
+
+    /// Reads the object starting at the given byte offset.
+    fn read_from(self: Box<Self>, offset: u64) -> BoxFuture<'static, io::Result<DataReader>>;
+}
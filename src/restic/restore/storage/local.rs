@@ -0,0 +1,234 @@
+use super::{DataReader, Storage, StoredData};
+use futures::future::BoxFuture;
+use std::{
+    fs::{self, File},
+    io::{self, Read, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncSeekExt;
+
+/// Marks objects which are still being written and only become visible once renamed
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Keeps restores in a directory on the local filesystem.
+///
+/// Objects are written next to their final location and renamed once they have been synced to disk,
+/// so that a crash never leaves a truncated object behind under its key.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+struct LocalData {
+    file: File,
+    size: u64,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root: PathBuf = root.as_ref().into();
+        fs::create_dir_all(&root)?;
+
+        Ok(Self { root })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    fn partial_path(&self, key: &str) -> PathBuf {
+        self.root.join(format!("{key}{PARTIAL_SUFFIX}"))
+    }
+
+    fn create_parent(path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes a rename within the directory of the path durable.
+    fn sync_parent(path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) => File::open(parent)?.sync_all(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Storage for LocalStorage {
+    fn read(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(key))
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path(key);
+        Self::create_parent(&path)?;
+
+        // Concurrent writes of the same key must not share their temporary file
+        let mut file = tempfile::Builder::new()
+            .prefix(".")
+            .suffix(PARTIAL_SUFFIX)
+            .tempfile_in(path.parent().unwrap_or(&self.root))?;
+
+        file.write_all(data)?;
+        file.as_file().sync_all()?;
+        file.persist(&path).map_err(|err| err.error)?;
+
+        Self::sync_parent(&path)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        // An object which is still being written is abandoned with its key
+        match fs::remove_file(self.partial_path(key)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+
+        fs::remove_file(self.path(key))
+    }
+
+    fn list(&self, directory: &str) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(self.path(directory)) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut keys = Vec::new();
+
+        for entry in entries {
+            let entry = entry?;
+
+            if entry.file_type()?.is_file() {
+                keys.push(format!(
+                    "{directory}/{}",
+                    entry.file_name().to_string_lossy()
+                ));
+            }
+        }
+
+        Ok(keys)
+    }
+
+    fn size(&self, key: &str) -> io::Result<u64> {
+        Ok(fs::metadata(self.path(key))?.len())
+    }
+
+    fn create(&self, key: &str) -> io::Result<File> {
+        let path = self.partial_path(key);
+        Self::create_parent(&path)?;
+
+        // Leftovers of an interrupted attempt are overwritten
+        File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    fn persist(&self, key: &str, file: File) -> io::Result<()> {
+        file.sync_all()?;
+
+        let path = self.path(key);
+        fs::rename(self.partial_path(key), &path)?;
+
+        Self::sync_parent(&path)
+    }
+
+    fn reader(&self, key: &str) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(self.path(key))?))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let path = self.path(to);
+        Self::create_parent(&path)?;
+        fs::rename(self.path(from), &path)?;
+
+        Self::sync_parent(&path)
+    }
+
+    fn recover(&self) -> io::Result<()> {
+        for directory in fs::read_dir(&self.root)? {
+            let directory = directory?;
+
+            if !directory.file_type()?.is_dir() {
+                continue;
+            }
+
+            for entry in fs::read_dir(directory.path())? {
+                let entry = entry?;
+
+                if entry
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(PARTIAL_SUFFIX)
+                {
+                    eprintln!("Removing partially written object at {:?}", entry.path());
+                    fs::remove_file(entry.path())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn open(&self, key: &str) -> io::Result<Box<dyn StoredData>> {
+        let file = File::open(self.path(key))?;
+        let size = file.metadata()?.len();
+
+        Ok(Box::new(LocalData { file, size }))
+    }
+}
+
+impl StoredData for LocalData {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_from(self: Box<Self>, offset: u64) -> BoxFuture<'static, io::Result<DataReader>> {
+        Box::pin(async move {
+            let mut file = tokio::fs::File::from_std(self.file);
+            file.seek(SeekFrom::Start(offset)).await?;
+
+            Ok(Box::pin(file) as DataReader)
+        })
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn hide_objects_until_persisted() {
+        let root = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(root.path()).unwrap();
+
+        let mut file = storage.create("data/a.bin").unwrap();
+        file.write_all(b"data").unwrap();
+        assert!(!storage.exists("data/a.bin").unwrap());
+        assert!(storage
+            .list("data")
+            .unwrap()
+            .iter()
+            .all(|key| !key.ends_with(".bin")));
+
+        storage.persist("data/a.bin", file).unwrap();
+        assert_eq!(storage.read("data/a.bin").unwrap(), b"data");
+    }
+
+    #[test]
+    fn remove_partial_objects_on_recovery() {
+        let root = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(root.path()).unwrap();
+
+        storage.write("meta/a.json", b"{}").unwrap();
+        storage.create("data/a.bin").unwrap();
+        storage.recover().unwrap();
+
+        assert_eq!(storage.list("data").unwrap(), Vec::<String>::new());
+        assert_eq!(storage.list("meta").unwrap(), vec!["meta/a.json"]);
+    }
+}
@@ -0,0 +1,491 @@
+use super::{DataReader, Storage, StoredData};
+use crate::helper::content_disposition;
+use futures::{future::BoxFuture, TryStreamExt};
+use reqwest::{header, Client, RequestBuilder, Response, StatusCode, Url};
+use rusty_s3::{actions::ListObjectsV2, Bucket, Credentials, S3Action, UrlStyle};
+use std::{
+    collections::HashMap,
+    fs::File,
+    future::Future,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::runtime::Handle;
+use tokio_util::io::StreamReader;
+
+/// Validity of the signed URLs through which our own requests are authenticated
+const SIGNATURE_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// Connection details of an S3 compatible bucket, e.g. hosted by MinIO.
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    /// Base URL of the service, the bucket is addressed path-style below it
+    pub endpoint: Url,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Prepended to all object keys
+    pub prefix: String,
+    /// Lets clients download data objects through presigned URLs
+    pub presign: bool,
+}
+
+/// Keeps restores in an S3 compatible bucket.
+///
+/// Data objects are staged in a temporary file and uploaded once they are complete.
+/// Objects below the prefix are listed once and then tracked in memory, which assumes
+/// that no one but this server modifies them.
+pub struct S3Storage {
+    client: Client,
+    handle: Handle,
+    bucket: Bucket,
+    credentials: Credentials,
+    prefix: String,
+    presign: bool,
+    staging: PathBuf,
+    objects: Mutex<Option<ObjectCache>>,
+}
+
+/// Objects known to exist below the prefix, so that purges do not have to ask the bucket over and over.
+struct ObjectCache {
+    sizes: HashMap<String, u64>,
+    /// Contents of the records which have been read or written so far
+    records: HashMap<String, Vec<u8>>,
+}
+
+struct S3Data {
+    request: RequestBuilder,
+    size: u64,
+}
+
+impl S3Storage {
+    /// Must be called from within a Tokio runtime which is used to drive the requests.
+    pub fn new(config: S3Config, staging: impl Into<PathBuf>) -> io::Result<Self> {
+        let staging = staging.into();
+        std::fs::create_dir_all(&staging)?;
+
+        let bucket = Bucket::new(
+            config.endpoint,
+            UrlStyle::Path,
+            config.bucket,
+            config.region,
+        )
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+
+        Ok(Self {
+            client: Client::new(),
+            handle: Handle::current(),
+            bucket,
+            credentials: Credentials::new(config.access_key, config.secret_key),
+            prefix: config.prefix,
+            presign: config.presign,
+            staging,
+            objects: Mutex::new(None),
+        })
+    }
+
+    fn object(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    fn get(&self, key: &str) -> RequestBuilder {
+        let object = self.object(key);
+        let action = self.bucket.get_object(Some(&self.credentials), &object);
+
+        self.client.get(action.sign(SIGNATURE_LIFETIME))
+    }
+
+    fn put(&self, key: &str) -> RequestBuilder {
+        let object = self.object(key);
+        let action = self.bucket.put_object(Some(&self.credentials), &object);
+
+        self.client.put(action.sign(SIGNATURE_LIFETIME))
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        tokio::task::block_in_place(|| self.handle.block_on(future))
+    }
+
+    async fn send(request: RequestBuilder) -> io::Result<Response> {
+        let response = request.send().await.map_err(io::Error::other)?;
+
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::NOT_FOUND => Err(not_found()),
+            status => {
+                let body = response.text().await.unwrap_or_default();
+                Err(io::Error::other(format!(
+                    "S3 request failed ({status}): {body}"
+                )))
+            }
+        }
+    }
+
+    /// Runs the closure on the known objects, listing the bucket when called for the first time.
+    fn objects<T>(&self, f: impl FnOnce(&mut ObjectCache) -> T) -> io::Result<T> {
+        let mut objects = self.objects.lock().expect("S3 object cache poisoned");
+
+        let objects = match &mut *objects {
+            Some(objects) => objects,
+            None => objects.insert(ObjectCache {
+                sizes: self.list_sizes()?,
+                records: HashMap::new(),
+            }),
+        };
+
+        Ok(f(objects))
+    }
+
+    /// Sizes of all objects below the prefix, by their key.
+    fn list_sizes(&self) -> io::Result<HashMap<String, u64>> {
+        self.block_on(async {
+            let mut sizes = HashMap::new();
+            let mut continuation: Option<String> = None;
+
+            loop {
+                let mut action = self.bucket.list_objects_v2(Some(&self.credentials));
+                action.with_prefix(self.prefix.as_str());
+
+                if let Some(token) = continuation.take() {
+                    action.with_continuation_token(token);
+                }
+
+                let body = Self::send(self.client.get(action.sign(SIGNATURE_LIFETIME)))
+                    .await?
+                    .bytes()
+                    .await
+                    .map_err(io::Error::other)?;
+
+                let page = ListObjectsV2::parse_response(&body)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+                for object in page.contents {
+                    if let Some(key) = object.key.strip_prefix(&self.prefix) {
+                        sizes.insert(key.to_string(), object.size);
+                    }
+                }
+
+                match page.next_continuation_token {
+                    Some(token) => continuation = Some(token),
+                    None => break Ok(sizes),
+                }
+            }
+        })
+    }
+}
+
+impl Storage for S3Storage {
+    fn read(&self, key: &str) -> io::Result<Vec<u8>> {
+        let (exists, cached) = self.objects(|objects| {
+            (
+                objects.sizes.contains_key(key),
+                objects.records.get(key).cloned(),
+            )
+        })?;
+
+        match (exists, cached) {
+            (false, _) => return Err(not_found()),
+            (true, Some(record)) => return Ok(record),
+            (true, None) => {}
+        }
+
+        let record = self.block_on(async {
+            let response = Self::send(self.get(key)).await?;
+            let bytes = response.bytes().await.map_err(io::Error::other)?;
+            Ok::<_, io::Error>(bytes.to_vec())
+        })?;
+
+        self.objects(|objects| objects.records.insert(key.to_string(), record.clone()))?;
+
+        Ok(record)
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        self.block_on(Self::send(self.put(key).body(data.to_vec())))?;
+
+        self.objects(|objects| {
+            objects.sizes.insert(key.to_string(), data.len() as u64);
+            objects.records.insert(key.to_string(), data.to_vec());
+        })
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        let object = self.object(key);
+        let action = self.bucket.delete_object(Some(&self.credentials), &object);
+
+        self.block_on(Self::send(
+            self.client.delete(action.sign(SIGNATURE_LIFETIME)),
+        ))?;
+
+        self.objects(|objects| {
+            objects.sizes.remove(key);
+            objects.records.remove(key);
+        })
+    }
+
+    fn list(&self, directory: &str) -> io::Result<Vec<String>> {
+        let prefix = format!("{directory}/");
+
+        self.objects(|objects| {
+            objects
+                .sizes
+                .keys()
+                .filter(|key| key.starts_with(&prefix))
+                .cloned()
+                .collect()
+        })
+    }
+
+    fn size(&self, key: &str) -> io::Result<u64> {
+        self.objects(|objects| objects.sizes.get(key).copied())?
+            .ok_or_else(not_found)
+    }
+
+    fn create(&self, _key: &str) -> io::Result<File> {
+        tempfile::tempfile_in(&self.staging)
+    }
+
+    fn persist(&self, key: &str, mut file: File) -> io::Result<()> {
+        let size = file.seek(SeekFrom::End(0))?;
+        file.seek(SeekFrom::Start(0))?;
+
+        let body = tokio_util::io::ReaderStream::new(tokio::fs::File::from_std(file));
+        let request = self
+            .put(key)
+            .header(header::CONTENT_LENGTH, size)
+            .body(reqwest::Body::wrap_stream(body));
+
+        self.block_on(Self::send(request))?;
+
+        self.objects(|objects| {
+            objects.sizes.insert(key.to_string(), size);
+            objects.records.remove(key);
+        })
+    }
+
+    fn open(&self, key: &str) -> io::Result<Box<dyn StoredData>> {
+        Ok(Box::new(S3Data {
+            size: self.size(key)?,
+            request: self.get(key),
+        }))
+    }
+
+    fn reader(&self, key: &str) -> io::Result<Box<dyn Read + Send>> {
+        // Staged locally since reading the response requires the runtime to be driven
+        let mut file = tempfile::tempfile_in(&self.staging)?;
+
+        self.block_on(async {
+            let mut response = Self::send(self.get(key)).await?;
+
+            while let Some(chunk) = response.chunk().await.map_err(io::Error::other)? {
+                file.write_all(&chunk)?;
+            }
+
+            Ok::<_, io::Error>(())
+        })?;
+
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(Box::new(file))
+    }
+
+    fn serves_directly(&self) -> bool {
+        self.presign
+    }
+
+    fn presign(&self, key: &str, file_name: &str, lifetime: Duration) -> Option<String> {
+        if !self.presign {
+            return None;
+        }
+
+        let object = self.object(key);
+        let mut action = self.bucket.get_object(Some(&self.credentials), &object);
+        action.query_mut().insert(
+            "response-content-disposition",
+            content_disposition(file_name),
+        );
+
+        Some(action.sign(lifetime).to_string())
+    }
+}
+
+impl StoredData for S3Data {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_from(self: Box<Self>, offset: u64) -> BoxFuture<'static, io::Result<DataReader>> {
+        Box::pin(async move {
+            let response = S3Storage::send(
+                self.request
+                    .header(header::RANGE, format!("bytes={offset}-")),
+            )
+            .await?;
+
+            let stream = response.bytes_stream().map_err(io::Error::other);
+
+            Ok(Box::pin(StreamReader::new(stream)) as DataReader)
+        })
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "object does not exist")
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use rand::{rngs::OsRng, RngCore};
+    use std::env;
+    use tokio::io::AsyncReadExt;
+
+    /// Connects to the bucket given through `S3_TEST_ENDPOINT`, `S3_TEST_BUCKET`, `S3_TEST_ACCESS_KEY` and
+    /// `S3_TEST_SECRET_KEY`, e.g. of a MinIO started with `docker run -p 9000:9000 minio/minio server /data`
+    /// and its default credentials. Every call uses a new prefix, so that tests start out with an empty bucket.
+    fn connect(staging: &tempfile::TempDir, prefix: Option<&str>) -> (S3Storage, String) {
+        let variable = |name: &str| env::var(name).unwrap_or_else(|_| panic!("{name} is not set"));
+
+        let mut random = [0; 8];
+        OsRng.fill_bytes(&mut random);
+        let prefix = prefix.map_or_else(|| format!("test-{}/", hex::encode(random)), Into::into);
+
+        let config = S3Config {
+            endpoint: variable("S3_TEST_ENDPOINT").parse().unwrap(),
+            bucket: variable("S3_TEST_BUCKET"),
+            region: "us-east-1".into(),
+            access_key: variable("S3_TEST_ACCESS_KEY"),
+            secret_key: variable("S3_TEST_SECRET_KEY"),
+            prefix: prefix.clone(),
+            presign: true,
+        };
+
+        let storage = S3Storage::new(config, staging.path()).unwrap();
+
+        // Fails if the bucket exists already, which is just as good
+        let create = storage.bucket.create_bucket(&storage.credentials);
+        storage
+            .block_on(storage.client.put(create.sign(SIGNATURE_LIFETIME)).send())
+            .ok();
+
+        (storage, prefix)
+    }
+
+    /// Storage for a bucket which is never contacted, since building and signing URLs does not need a network.
+    fn offline(staging: &tempfile::TempDir, presign: bool) -> S3Storage {
+        let config = S3Config {
+            endpoint: "http://localhost:9000".parse().unwrap(),
+            bucket: "restores".into(),
+            region: "us-east-1".into(),
+            access_key: "access".into(),
+            secret_key: "secret".into(),
+            prefix: "staging/".into(),
+            presign,
+        };
+
+        S3Storage::new(config, staging.path()).unwrap()
+    }
+
+    fn query(url: &Url, name: &str) -> Option<String> {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    #[tokio::test]
+    async fn address_objects_below_the_prefix() {
+        let staging = tempfile::tempdir().unwrap();
+        let storage = offline(&staging, false);
+
+        assert_eq!(storage.object("meta/a.json"), "staging/meta/a.json");
+
+        for request in [storage.get("data/a.bin"), storage.put("data/a.bin")] {
+            let url = request.build().unwrap().url().clone();
+
+            assert_eq!(url.host_str(), Some("localhost"));
+            assert_eq!(url.path(), "/restores/staging/data/a.bin");
+            assert!(query(&url, "X-Amz-Signature").is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn presign_only_when_enabled() {
+        let staging = tempfile::tempdir().unwrap();
+        let storage = offline(&staging, false);
+
+        assert!(!storage.serves_directly());
+        assert_eq!(
+            storage.presign("data/a.bin", "a.zip", Duration::from_secs(60)),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn presign_downloads_under_their_file_name() {
+        let staging = tempfile::tempdir().unwrap();
+        let storage = offline(&staging, true);
+
+        let url = storage
+            .presign(
+                "data/a.bin",
+                "Bericht \"März\".zip",
+                Duration::from_secs(90),
+            )
+            .unwrap();
+        let url = Url::parse(&url).unwrap();
+
+        assert!(storage.serves_directly());
+        assert_eq!(url.path(), "/restores/staging/data/a.bin");
+        assert_eq!(query(&url, "X-Amz-Expires").as_deref(), Some("90"));
+        assert_eq!(
+            query(&url, "response-content-disposition"),
+            Some(content_disposition("Bericht \"März\".zip"))
+        );
+        assert!(query(&url, "X-Amz-Credential")
+            .unwrap()
+            .starts_with("access/"));
+        assert!(query(&url, "X-Amz-Signature").is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "requires an S3 compatible server, see `connect`"]
+    async fn store_records_and_data_in_a_bucket() {
+        let staging = tempfile::tempdir().unwrap();
+        let (storage, prefix) = connect(&staging, None);
+
+        storage.write("meta/a.json", b"{}").unwrap();
+        assert_eq!(storage.read("meta/a.json").unwrap(), b"{}");
+
+        let mut file = storage.create("data/a.bin").unwrap();
+        file.write_all(b"restored data").unwrap();
+        storage.persist("data/a.bin", file).unwrap();
+
+        let mut reader = storage
+            .open("data/a.bin")
+            .unwrap()
+            .read_from(9)
+            .await
+            .unwrap();
+        let mut tail = String::new();
+        reader.read_to_string(&mut tail).await.unwrap();
+        assert_eq!(tail, "data");
+
+        let url = storage.presign("data/a.bin", "a.zip", Duration::from_secs(60));
+        let presigned = reqwest::get(url.unwrap()).await.unwrap();
+        assert_eq!(presigned.bytes().await.unwrap(), "restored data");
+
+        // Another server process learns about the objects by listing the bucket
+        let (restarted, _) = connect(&staging, Some(&prefix));
+        assert_eq!(restarted.list("meta").unwrap(), vec!["meta/a.json"]);
+        assert_eq!(restarted.size("data/a.bin").unwrap(), 13);
+        assert_eq!(restarted.read("meta/a.json").unwrap(), b"{}");
+
+        restarted.delete("meta/a.json").unwrap();
+        restarted.delete("data/a.bin").unwrap();
+        assert!(!restarted.exists("data/a.bin").unwrap());
+        assert!(restarted.read("meta/a.json").is_err());
+    }
+}
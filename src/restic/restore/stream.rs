@@ -0,0 +1,163 @@
+use std::{
+    io::{self, Write},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::{self, error::TrySendError, OwnedPermit};
+
+/// Chunks which may be in flight to a download before the restore waits for it to catch up.
+const STREAM_CAPACITY: usize = 16;
+/// Time the restore waits for a download to catch up before leaving it behind and finishing on its own
+const STALL_TIMEOUT: Duration = Duration::from_secs(30);
+const STALL_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+type Chunk = io::Result<Vec<u8>>;
+
+/// Receives the data of a restore while it is being written, ending with an error if the restore fails.
+pub type DataReceiver = mpsc::Receiver<Chunk>;
+
+/// Hands the data of a restore to a download, which can always be aborted without waiting for it.
+pub(super) struct DataSender {
+    sender: mpsc::Sender<Chunk>,
+    /// Slot kept free for the error ending an aborted download
+    abort: OwnedPermit<Chunk>,
+    stall_timeout: Duration,
+}
+
+pub(super) fn channel() -> (DataSender, DataReceiver) {
+    channel_with_timeout(STALL_TIMEOUT)
+}
+
+fn channel_with_timeout(stall_timeout: Duration) -> (DataSender, DataReceiver) {
+    let (sender, receiver) = mpsc::channel(STREAM_CAPACITY + 1);
+    let abort = sender
+        .clone()
+        .try_reserve_owned()
+        .expect("new channel has capacity");
+
+    let sender = DataSender {
+        sender,
+        abort,
+        stall_timeout,
+    };
+
+    (sender, receiver)
+}
+
+impl DataSender {
+    /// Passes the chunk on, returning whether the download is still there and has kept up within the stall timeout.
+    fn send(&self, mut chunk: Chunk) -> bool {
+        let deadline = Instant::now() + self.stall_timeout;
+
+        loop {
+            match self.sender.try_send(chunk) {
+                Ok(()) => return true,
+                Err(TrySendError::Closed(_)) => return false,
+                Err(TrySendError::Full(_)) if Instant::now() >= deadline => return false,
+                Err(TrySendError::Full(returned)) => {
+                    chunk = returned;
+                    sleep(STALL_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    /// Ends the download with the error, instead of letting it end as if the data was complete.
+    pub fn abort(self, error: io::Error) {
+        self.abort.send(Err(error));
+    }
+}
+
+/// Hands a copy of everything written to the download receiving it, until the download goes away
+/// or stops keeping up, in which case it is aborted.
+pub(super) struct TeeWriter<'s, W: Write> {
+    writer: W,
+    stream: &'s mut Option<DataSender>,
+}
+
+impl<'s, W: Write> TeeWriter<'s, W> {
+    pub fn new(writer: W, stream: &'s mut Option<DataSender>) -> Self {
+        Self { writer, stream }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<'s, W: Write> Write for TeeWriter<'s, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+
+        // The restore is still completed for later downloads once nobody is listening anymore,
+        // and must not be held up by downloads which do not read what they are sent
+        if let Some(stream) = self.stream.as_ref() {
+            if !stream.send(Ok(buf[..written].to_vec())) {
+                if let Some(stream) = self.stream.take() {
+                    stream.abort(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "download did not keep up with the restore",
+                    ));
+                }
+            }
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn pass_written_data_to_the_download() {
+        let (sender, mut receiver) = channel();
+        let mut stream = Some(sender);
+        let mut writer = TeeWriter::new(Vec::new(), &mut stream);
+
+        writer.write_all(b"first").unwrap();
+        writer.write_all(b"second").unwrap();
+
+        assert_eq!(writer.into_inner(), b"firstsecond");
+        assert_eq!(receiver.blocking_recv().unwrap().unwrap(), b"first");
+        assert_eq!(receiver.blocking_recv().unwrap().unwrap(), b"second");
+    }
+
+    #[test]
+    fn keep_writing_once_the_download_is_gone() {
+        let (sender, receiver) = channel();
+        let mut stream = Some(sender);
+        let mut writer = TeeWriter::new(Vec::new(), &mut stream);
+        drop(receiver);
+
+        writer.write_all(b"for later downloads").unwrap();
+
+        assert_eq!(writer.into_inner(), b"for later downloads");
+    }
+
+    #[test]
+    fn abort_downloads_which_do_not_keep_up() {
+        let (sender, mut receiver) = channel_with_timeout(Duration::from_millis(10));
+        let mut stream = Some(sender);
+        let mut writer = TeeWriter::new(Vec::new(), &mut stream);
+
+        for _ in 0..=STREAM_CAPACITY {
+            writer.write_all(b"chunk").unwrap();
+        }
+
+        assert_eq!(writer.into_inner().len(), (STREAM_CAPACITY + 1) * 5);
+        assert!(stream.is_none());
+
+        for _ in 0..STREAM_CAPACITY {
+            assert!(receiver.blocking_recv().unwrap().is_ok());
+        }
+
+        assert!(receiver.blocking_recv().unwrap().is_err());
+        assert!(receiver.blocking_recv().is_none());
+    }
+}